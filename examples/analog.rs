@@ -18,14 +18,14 @@ fn main() {
 
     let pin = 14; // A0
 
-    b.set_pin_mode(pin, firmata_rs::PIN_MODE_ANALOG)
-        .expect("pin mode set");
+    b.set_pin_mode(pin, PinMode::Analog).expect("pin mode set");
 
     b.report_analog(pin, 1).expect("reporting state");
 
     loop {
-        b.read_and_decode().expect("a message");
-        tracing::info!("analog value: {}", b.pins[pin as usize].value);
+        if let Message::Analog { value, .. } = b.read_and_decode().expect("a message") {
+            tracing::info!("analog value: {}", value);
+        }
         thread::sleep(Duration::from_millis(10));
     }
 }
@@ -18,20 +18,12 @@ fn set_rgb<T: firmata_rs::Firmata>(board: &Arc<Mutex<T>>, rgb: [u8; 3]) {
 }
 
 fn read_rgb<T: firmata_rs::Firmata>(board: &Arc<Mutex<T>>) -> Vec<u8> {
-    {
-        let mut b = board.lock().expect("lock");
-        b.retry_i2c_write(0x09, "g".as_bytes()).expect("i2c write");
-        b.retry_i2c_read(0x09, 3).expect("i2c read");
-    }
-    loop {
-        {
-            let mut b = board.lock().expect("lock");
-            if b.i2c_data().iter().count() > 0 {
-                return b.i2c_data().pop().expect("i2c data").data;
-            }
-        }
-        thread::sleep(Duration::from_millis(10));
-    }
+    // "g" asks for the current color, so read it like a register.
+    board
+        .lock()
+        .expect("lock")
+        .retry_i2c_read_register(0x09, 'g' as i32, 3, Duration::from_millis(1000))
+        .expect("i2c read")
 }
 
 fn main() {
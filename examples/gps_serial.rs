@@ -0,0 +1,36 @@
+use firmata_rs::*;
+use serialport::*;
+use std::io::{BufRead, BufReader};
+use std::time::Duration;
+
+fn main() {
+    tracing_subscriber::fmt::init();
+
+    let port = serialport::new("/dev/ttyACM0", 57_600)
+        .data_bits(DataBits::Eight)
+        .parity(Parity::None)
+        .stop_bits(StopBits::One)
+        .flow_control(FlowControl::None)
+        .timeout(Duration::from_millis(1000))
+        .open()
+        .expect("an opened serial port");
+
+    let mut b = firmata_rs::Board::new(Box::new(port)).expect("new board");
+
+    // GPS module with its TX on pin 10 and RX on pin 11.
+    let gps = SerialPortId::Software(0);
+
+    b.serial_config(gps, 9600, Some((10, 11)))
+        .expect("serial config");
+    b.serial_read_continuously(gps, None).expect("serial read");
+
+    let mut lines = BufReader::new(b.serial(gps)).lines();
+    loop {
+        match lines.next() {
+            Some(Ok(line)) => tracing::info!("{}", line),
+            Some(Err(e)) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Some(Err(e)) => panic!("serial read: {e}"),
+            None => break,
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use firmata_rs::*;
+use serialport::*;
+use std::time::Duration;
+
+fn main() {
+    tracing_subscriber::fmt::init();
+
+    let port = serialport::new("/dev/ttyACM0", 57_600)
+        .data_bits(DataBits::Eight)
+        .parity(Parity::None)
+        .stop_bits(StopBits::One)
+        .flow_control(FlowControl::None)
+        .timeout(Duration::from_millis(1000))
+        .open()
+        .expect("an opened serial port");
+
+    let mut b = firmata_rs::Board::new(Box::new(port)).expect("new board");
+
+    b.retry_set_pin_mode(13, PinMode::Output)
+        .expect("pin mode set");
+
+    // The board blinks the LED on its own, without any further messages from the host.
+    let task = [
+        Command::SetDigitalPinValue {
+            pin: 13,
+            value: true,
+        },
+        Command::SchedulerDelayTask { delay: 400 },
+        Command::SetDigitalPinValue {
+            pin: 13,
+            value: false,
+        },
+        Command::SchedulerDelayTask { delay: 400 },
+    ];
+
+    b.retry_scheduler_create_task_from(0, &task)
+        .expect("task created");
+    b.retry_scheduler_schedule_task(0, Duration::ZERO)
+        .expect("task scheduled");
+
+    b.retry_query_scheduler_task(0).expect("task queried");
+    loop {
+        if let Ok(message @ Message::SchedulerTaskReply { .. }) = b.read_and_decode() {
+            tracing::info!("{:?}", message);
+            break;
+        }
+    }
+}
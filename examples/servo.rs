@@ -18,14 +18,15 @@ fn main() {
 
     let pin = 3;
 
-    b.retry_set_pin_mode(pin, firmata_rs::PIN_MODE_SERVO)
-        .expect("pin mode set");
+    b.retry_servo_config(pin, SERVO_MIN_PULSE.into(), SERVO_MAX_PULSE.into())
+        .expect("servo config");
 
     tracing::info!("Starting loop...");
 
     loop {
-        for value in 0..180 {
-            b.retry_analog_write(pin, value).expect("analog write");
+        for value in 0..=180 {
+            b.retry_servo_write_degrees(pin, value)
+                .expect("servo write");
             tracing::info!("{}", value);
             thread::sleep(Duration::from_millis(10));
         }
@@ -0,0 +1,75 @@
+//! Steppers driven by the `ACCELSTEPPER_DATA` messages of ConfigurableFirmata.
+
+/// How an AccelStepper is wired to the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccelStepperInterface {
+    /// Step and direction driver, such as the A4988.
+    Driver { step_pin: u8, direction_pin: u8 },
+    /// Two wire H-bridge.
+    TwoWire { pins: [u8; 2] },
+    /// Three wire H-bridge.
+    ThreeWire { pins: [u8; 3] },
+    /// Four wire H-bridge.
+    FourWire { pins: [u8; 4] },
+}
+impl AccelStepperInterface {
+    /// All motor pins the stepper uses, without the enable pin.
+    pub fn pins(&self) -> Vec<u8> {
+        match self {
+            AccelStepperInterface::Driver {
+                step_pin,
+                direction_pin,
+            } => vec![*step_pin, *direction_pin],
+            AccelStepperInterface::TwoWire { pins } => pins.to_vec(),
+            AccelStepperInterface::ThreeWire { pins } => pins.to_vec(),
+            AccelStepperInterface::FourWire { pins } => pins.to_vec(),
+        }
+    }
+}
+
+/// Step size of an AccelStepper.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum StepSize {
+    #[default]
+    Whole,
+    Half,
+    Quarter,
+}
+
+/// Configuration of an AccelStepper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AccelStepperConfig {
+    pub interface: AccelStepperInterface,
+    pub step_size: StepSize,
+    /// Pin that enables the driver, if it has one.
+    pub enable_pin: Option<u8>,
+    /// Bitmask of inverted pins: bits 0 to 3 for the motor pins in order, bit 4 for the enable
+    /// pin.
+    pub invert: u8,
+}
+impl AccelStepperConfig {
+    /// Creates a new `AccelStepperConfig` with whole steps, no enable pin and no inverted pins.
+    pub fn new(interface: AccelStepperInterface) -> Self {
+        Self {
+            interface,
+            step_size: StepSize::Whole,
+            enable_pin: None,
+            invert: 0,
+        }
+    }
+    /// Use `step_size` steps.
+    pub fn step_size(mut self, step_size: StepSize) -> Self {
+        self.step_size = step_size;
+        self
+    }
+    /// Enable the driver through `enable_pin`.
+    pub fn enable_pin(mut self, enable_pin: u8) -> Self {
+        self.enable_pin = Some(enable_pin);
+        self
+    }
+    /// Invert the pins in the `invert` bitmask.
+    pub fn invert(mut self, invert: u8) -> Self {
+        self.invert = invert;
+        self
+    }
+}
@@ -0,0 +1,45 @@
+//! Supported pin modes and resolutions as reported by the board.
+
+use crate::PinMode;
+
+/// A mode supported by a pin, with the resolution in bits the pin has in that mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ModeCapability {
+    pub mode: PinMode,
+    pub resolution: u8,
+}
+
+/// Supported modes and resolutions of every pin, indexed by the firmware's pin numbers.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    pub pins: Vec<Vec<ModeCapability>>,
+}
+impl Capabilities {
+    /// Number of pins on the board.
+    pub fn len(&self) -> usize {
+        self.pins.len()
+    }
+    /// Whether the board reported no pins at all.
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+    /// All modes supported by `pin`, empty if the pin does not exist or supports no modes.
+    pub fn modes(&self, pin: u8) -> &[ModeCapability] {
+        self.pins.get(pin as usize).map_or(&[], |modes| modes)
+    }
+    /// Whether `pin` supports `mode`.
+    pub fn supports(&self, pin: u8, mode: PinMode) -> bool {
+        self.resolution(pin, mode).is_some()
+    }
+    /// Resolution in bits of `pin` in `mode`, if the pin supports it.
+    pub fn resolution(&self, pin: u8, mode: PinMode) -> Option<u8> {
+        self.modes(pin)
+            .iter()
+            .find(|capability| capability.mode == mode)
+            .map(|capability| capability.resolution)
+    }
+    /// All pins that support `mode`.
+    pub fn pins_supporting(&self, mode: PinMode) -> impl Iterator<Item = u8> + '_ {
+        (0..self.pins.len() as u8).filter(move |&pin| self.supports(pin, mode))
+    }
+}
@@ -0,0 +1,881 @@
+//! Sans-IO Firmata message encoding and decoding.
+//!
+//! The [`Decoder`] turns raw bytes received from a Firmata device into [`Message`]s without
+//! touching any I/O, and [`Command::encode`] builds the byte frames for every command the host
+//! sends. [`Board`](crate::Board) is built on top of both, but they can be used on their own to
+//! put Firmata behind any other transport.
+
+use crate::*;
+
+/// Incremental Firmata decoder.
+///
+/// Feed it bytes as they arrive and call [`Decoder::decode`] until it returns `Ok(None)`, which
+/// means more bytes are needed to complete the next message.
+///
+/// The decoder resynchronizes on its own: data bytes that do not follow a command byte, unknown
+/// command bytes and messages cut short by the next command byte are discarded and counted in
+/// [`Decoder::skipped`].
+#[derive(Clone, Debug, Default)]
+pub struct Decoder {
+    buf: Vec<u8>,
+    skipped: usize,
+}
+impl Decoder {
+    /// Creates a new, empty `Decoder`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Append received `bytes` to the internal buffer.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+    /// Number of buffered bytes that have not been decoded yet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+    /// Total number of bytes discarded while resynchronizing.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+    /// Decode the next complete message in the buffer, if any.
+    ///
+    /// Bytes belonging to a message that fails to decode are consumed, so decoding can continue
+    /// after an error.
+    pub fn decode(&mut self) -> Result<Option<Message>> {
+        loop {
+            // Messages always start with a command byte.
+            let start = self
+                .buf
+                .iter()
+                .position(|&b| is_command(b))
+                .unwrap_or(self.buf.len());
+            self.skip(start);
+
+            let Some(&command) = self.buf.first() else {
+                return Ok(None);
+            };
+            let next = self.buf.iter().skip(1).position(|&b| is_command(b));
+            let len = match (command, next) {
+                (
+                    REPORT_VERSION
+                    | ANALOG_MESSAGE..=ANALOG_MESSAGE_BOUND
+                    | DIGITAL_MESSAGE..=DIGITAL_MESSAGE_BOUND,
+                    Some(next),
+                ) if next < 2 => {
+                    // Cut short by the next command.
+                    self.skip(next + 1);
+                    continue;
+                }
+                (
+                    REPORT_VERSION
+                    | ANALOG_MESSAGE..=ANALOG_MESSAGE_BOUND
+                    | DIGITAL_MESSAGE..=DIGITAL_MESSAGE_BOUND,
+                    _,
+                ) => 3,
+                (START_SYSEX, Some(next)) if self.buf[next + 1] == END_SYSEX => next + 2,
+                (START_SYSEX, Some(next)) => {
+                    // Cut short by the next command, which may be a new SysEx.
+                    self.skip(next + 1);
+                    continue;
+                }
+                (START_SYSEX, None) => return Ok(None),
+                _ => {
+                    self.skip(1);
+                    continue;
+                }
+            };
+            if self.buf.len() < len {
+                return Ok(None);
+            }
+            let frame: Vec<u8> = self.buf.drain(..len).collect();
+            return decode_frame(&frame).map(Some);
+        }
+    }
+    /// Discard the first `n` buffered bytes.
+    fn skip(&mut self, n: usize) {
+        if n > 0 {
+            tracing::warn!("skipped {n} byte(s) to resynchronize: {:?}", &self.buf[..n]);
+            self.buf.drain(..n);
+            self.skipped += n;
+        }
+    }
+}
+
+/// Whether `byte` is a command byte rather than a 7-bit data byte.
+fn is_command(byte: u8) -> bool {
+    byte & 0x80 != 0
+}
+
+/// Decode a single complete message `frame`.
+fn decode_frame(buf: &[u8]) -> Result<Message> {
+    match buf[0] {
+        REPORT_VERSION => Ok(Message::ProtocolVersion(ProtocolVersion::new(
+            buf[1], buf[2],
+        ))),
+        ANALOG_MESSAGE..=ANALOG_MESSAGE_BOUND => Ok(Message::Analog {
+            channel: buf[0] & 0x0F,
+            value: (buf[1] as i32) | ((buf[2] as i32) << 7),
+        }),
+        DIGITAL_MESSAGE..=DIGITAL_MESSAGE_BOUND => Ok(Message::Digital {
+            port: buf[0] & 0x0F,
+            mask: buf[1] | (buf[2] << 7),
+        }),
+        START_SYSEX => decode_sysex(buf),
+        byte => Err(Error::BadByte { byte }),
+    }
+}
+
+/// Decode a SysEx `buf`, including the `START_SYSEX` and `END_SYSEX` bytes.
+fn decode_sysex(buf: &[u8]) -> Result<Message> {
+    match buf[1] {
+        END_SYSEX => Ok(Message::EmptyResponse),
+        ANALOG_MAPPING_RESPONSE => Ok(Message::AnalogMappingResponse(
+            buf[2..buf.len() - 1]
+                .iter()
+                .map(|&channel| (channel != 127u8).then_some(channel))
+                .collect(),
+        )),
+        CAPABILITY_RESPONSE => {
+            let data = &buf[2..buf.len() - 1];
+            let mut i = 0;
+            let mut capabilities = Capabilities::default();
+            let mut modes = vec![];
+            while i < data.len() {
+                // Completed a pin, push and continue.
+                if data[i] == 127u8 {
+                    capabilities.pins.push(std::mem::take(&mut modes));
+                    i += 1;
+                } else {
+                    let resolution = *data.get(i + 1).with_context(|| MessageTooShortSnafu)?;
+                    modes.push(ModeCapability {
+                        mode: data[i].try_into()?,
+                        resolution,
+                    });
+                    i += 2;
+                }
+            }
+            Ok(Message::CapabilityResponse(capabilities))
+        }
+        REPORT_FIRMWARE => {
+            let major = *buf.get(2).with_context(|| MessageTooShortSnafu)?;
+            let minor = *buf.get(3).with_context(|| MessageTooShortSnafu)?;
+            let mut name = String::new();
+            if 4 < buf.len() - 1 {
+                name = std::str::from_utf8(&decode_bytes(&buf[4..buf.len() - 1]))
+                    .with_context(|_| Utf8Snafu)?
+                    .to_string();
+            }
+            Ok(Message::ReportFirmware {
+                version: FirmwareVersion::new(major, minor),
+                name,
+            })
+        }
+        I2C_REPLY => {
+            let len = buf.len();
+            if len < 8 {
+                return Err(Error::MessageTooShort);
+            }
+            Ok(Message::I2CReply(I2CReply {
+                address: decode_u14(buf[2], buf[3]) as i32,
+                register: decode_u14(buf[4], buf[5]) as i32,
+                data: decode_bytes(&buf[6..len - 1]),
+            }))
+        }
+        ACCELSTEPPER_DATA => {
+            let data = &buf[2..buf.len() - 1];
+            ensure!(data.len() >= 2, MessageTooShortSnafu);
+            let position = || -> Result<i32> {
+                let bytes = data.get(2..7).with_context(|| MessageTooShortSnafu)?;
+                Ok(decode_i32(bytes.try_into().expect("5 bytes")))
+            };
+            match data[0] {
+                ACCELSTEPPER_REPORT_POSITION => Ok(Message::AccelStepperPosition {
+                    device: data[1],
+                    position: position()?,
+                }),
+                ACCELSTEPPER_MOVE_COMPLETE => Ok(Message::AccelStepperMoveComplete {
+                    device: data[1],
+                    position: position()?,
+                }),
+                MULTISTEPPER_MOVE_COMPLETE => {
+                    Ok(Message::MultiStepperMoveComplete { group: data[1] })
+                }
+                _ => Err(Error::UnknownSysEx {
+                    code: ACCELSTEPPER_DATA,
+                }),
+            }
+        }
+        ENCODER_DATA => Ok(Message::EncoderPositions(
+            buf[2..buf.len() - 1]
+                .chunks_exact(5)
+                .map(|position| {
+                    let magnitude = decode_u32(&position[1..]) as i32;
+                    EncoderPosition {
+                        encoder: position[0] & ENCODER_CHANNEL_MASK,
+                        position: match position[0] & ENCODER_DIRECTION_MASK {
+                            0 => magnitude,
+                            _ => -magnitude,
+                        },
+                    }
+                })
+                .collect(),
+        )),
+        ONEWIRE_DATA => {
+            let len = buf.len();
+            if len < 5 {
+                return Err(Error::MessageTooShort);
+            }
+            let pin = buf[3];
+            let data = decode_packed(&buf[4..len - 1]);
+            match buf[2] {
+                ONEWIRE_SEARCH_REPLY | ONEWIRE_SEARCH_ALARMS_REPLY => {
+                    Ok(Message::OneWireSearchReply {
+                        pin,
+                        alarms: buf[2] == ONEWIRE_SEARCH_ALARMS_REPLY,
+                        addresses: data
+                            .chunks_exact(8)
+                            .filter_map(|bytes| {
+                                let bytes = bytes.try_into().expect("8 bytes");
+                                OneWireAddress::new(bytes)
+                                    .inspect_err(|e| tracing::warn!("skipping OneWire device: {e}"))
+                                    .ok()
+                            })
+                            .collect(),
+                    })
+                }
+                ONEWIRE_READ_REPLY => {
+                    ensure!(data.len() >= 2, MessageTooShortSnafu);
+                    Ok(Message::OneWireReadReply {
+                        pin,
+                        correlation_id: u16::from_le_bytes([data[0], data[1]]),
+                        data: data[2..].to_vec(),
+                    })
+                }
+                _ => Err(Error::UnknownSysEx { code: ONEWIRE_DATA }),
+            }
+        }
+        STEPPER_DATA => Ok(Message::StepperMoveComplete {
+            device: *buf[2..buf.len() - 1]
+                .first()
+                .with_context(|| MessageTooShortSnafu)?,
+        }),
+        SCHEDULER_DATA => {
+            let data = &buf[2..buf.len() - 1];
+            let subcommand = *data.first().with_context(|| MessageTooShortSnafu)?;
+            match subcommand {
+                SCHEDULER_QUERY_ALL_TASKS_REPLY => Ok(Message::SchedulerTasks(data[1..].to_vec())),
+                SCHEDULER_QUERY_TASK_REPLY | SCHEDULER_ERROR_TASK_REPLY => {
+                    let id = *data.get(1).with_context(|| MessageTooShortSnafu)?;
+                    let task = SchedulerTask::decode(&decode_packed(&data[2..]));
+                    match subcommand {
+                        SCHEDULER_QUERY_TASK_REPLY => Ok(Message::SchedulerTaskReply { id, task }),
+                        _ => Ok(Message::SchedulerTaskError { id, task }),
+                    }
+                }
+                _ => Err(Error::UnknownSysEx {
+                    code: SCHEDULER_DATA,
+                }),
+            }
+        }
+        SERIAL_DATA => {
+            let len = buf.len();
+            if len < 4 {
+                return Err(Error::MessageTooShort);
+            }
+            match buf[2] & 0xF0 {
+                SERIAL_REPLY => Ok(Message::SerialReply {
+                    port: (buf[2] & 0x0F).try_into()?,
+                    data: decode_bytes(&buf[3..len - 1]),
+                }),
+                _ => Err(Error::UnknownSysEx { code: SERIAL_DATA }),
+            }
+        }
+        SPI_DATA => {
+            let len = buf.len();
+            if len < 7 {
+                return Err(Error::MessageTooShort);
+            }
+            match buf[2] {
+                SPI_REPLY => Ok(Message::SpiReply {
+                    device: buf[3].into(),
+                    request_id: buf[4],
+                    data: decode_bytes(&buf[6..len - 1]),
+                }),
+                _ => Err(Error::UnknownSysEx { code: SPI_DATA }),
+            }
+        }
+        SHIFT_DATA => {
+            let len = buf.len();
+            if len < 5 {
+                return Err(Error::MessageTooShort);
+            }
+            match buf[2] {
+                SHIFT_IN_REPLY => Ok(Message::ShiftInReply {
+                    data_pin: buf[3],
+                    data: decode_bytes(&buf[4..len - 1]),
+                }),
+                _ => Err(Error::UnknownSysEx { code: SHIFT_DATA }),
+            }
+        }
+        PIN_STATE_RESPONSE => {
+            let data = &buf[2..buf.len() - 1];
+            let pin = *data.first().with_context(|| MessageTooShortSnafu)?;
+            if data.len() < 3 {
+                return Ok(Message::PinStateResponse {
+                    pin,
+                    mode: None,
+                    value: 0,
+                });
+            }
+            // The value spans as many 7-bit bytes as its resolution needs.
+            Ok(Message::PinStateResponse {
+                pin,
+                mode: Some(data[1].try_into()?),
+                value: decode_u32(&data[2..]) as i32,
+            })
+        }
+        STRING_DATA => Ok(Message::StringData(
+            String::from_utf8_lossy(&decode_bytes(&buf[2..buf.len() - 1])).into_owned(),
+        )),
+        code => Err(Error::UnknownSysEx { code }),
+    }
+}
+
+/// Split a 14-bit `value` into its LSB and MSB 7-bit data bytes.
+pub(crate) fn encode_u14(value: u16) -> [u8; 2] {
+    [
+        value as u8 & SYSEX_REALTIME,
+        (value >> 7) as u8 & SYSEX_REALTIME,
+    ]
+}
+
+/// Join an `lsb` and `msb` 7-bit data byte pair into a 14-bit value.
+pub(crate) fn decode_u14(lsb: u8, msb: u8) -> u16 {
+    (lsb as u16 & 0x7F) | ((msb as u16 & 0x7F) << 7)
+}
+
+/// Split a `value` into as many 7-bit data bytes as it needs, LSB first.
+pub(crate) fn encode_u32(value: u32) -> Vec<u8> {
+    let mut value = value;
+    let mut buf = vec![];
+    loop {
+        buf.push(value as u8 & SYSEX_REALTIME);
+        value >>= 7;
+        if value == 0 {
+            return buf;
+        }
+    }
+}
+
+/// Join up to five 7-bit data bytes, LSB first, into a value.
+pub(crate) fn decode_u32(data: &[u8]) -> u32 {
+    data.iter()
+        .take(5)
+        .enumerate()
+        .fold(0, |value, (i, &byte)| {
+            value | ((byte as u32 & 0x7F) << (7 * i))
+        })
+}
+
+/// Encode every 8-bit byte in `data` as an LSB and MSB 7-bit data byte pair.
+pub(crate) fn encode_bytes(data: &[u8]) -> Vec<u8> {
+    data.iter()
+        .flat_map(|&byte| [byte & SYSEX_REALTIME, byte >> 7])
+        .collect()
+}
+
+/// Decode LSB and MSB 7-bit data byte pairs into 8-bit bytes, ignoring a trailing odd byte.
+pub(crate) fn decode_bytes(data: &[u8]) -> Vec<u8> {
+    data.chunks_exact(2)
+        .map(|pair| pair[0] | (pair[1] << 7))
+        .collect()
+}
+
+/// Pack 8-bit bytes in `data` into a stream of 7-bit data bytes, LSB first.
+pub(crate) fn encode_packed(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity((data.len() * 8).div_ceil(7));
+    let (mut bits, mut len) = (0u16, 0);
+    for &byte in data {
+        bits |= (byte as u16) << len;
+        len += 8;
+        while len >= 7 {
+            buf.push(bits as u8 & SYSEX_REALTIME);
+            bits >>= 7;
+            len -= 7;
+        }
+    }
+    if len > 0 {
+        buf.push(bits as u8 & SYSEX_REALTIME);
+    }
+    buf
+}
+
+/// Unpack a stream of 7-bit data bytes, LSB first, into 8-bit bytes, ignoring trailing padding.
+pub(crate) fn decode_packed(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(data.len() * 7 / 8);
+    let (mut bits, mut len) = (0u16, 0);
+    for &byte in data {
+        bits |= ((byte & SYSEX_REALTIME) as u16) << len;
+        len += 7;
+        if len >= 8 {
+            buf.push(bits as u8);
+            bits >>= 8;
+            len -= 8;
+        }
+    }
+    buf
+}
+
+/// Encode a `value` as five 7-bit data bytes, LSB first, with the sign at bit 3 of the last byte.
+///
+/// The magnitude has 31 bits, so `i32::MIN` is clamped to `-i32::MAX`.
+pub(crate) fn encode_i32(value: i32) -> [u8; 5] {
+    let magnitude = value.unsigned_abs().min(i32::MAX as u32);
+    let mut buf = [0; 5];
+    for (i, byte) in buf.iter_mut().enumerate() {
+        *byte = (magnitude >> (7 * i)) as u8 & 0x7F;
+    }
+    buf[4] &= 0x07;
+    if value < 0 {
+        buf[4] |= 0x08;
+    }
+    buf
+}
+
+/// Decode five 7-bit data bytes encoded with [`encode_i32`].
+pub(crate) fn decode_i32(data: &[u8; 5]) -> i32 {
+    let magnitude = data[..4]
+        .iter()
+        .enumerate()
+        .fold((data[4] as u32 & 0x07) << 28, |value, (i, &byte)| {
+            value | (byte as u32 & 0x7F) << (7 * i)
+        });
+    match data[4] & 0x08 {
+        0 => magnitude as i32,
+        _ => (magnitude as i32).wrapping_neg(),
+    }
+}
+
+/// Largest significand of the custom float format.
+const MAX_SIGNIFICAND: f64 = (1 << 23) as f64;
+
+/// Encode a `value` in the custom float format of AccelStepperFirmata: a 23-bit significand,
+/// a 4-bit power of ten exponent biased by 11 and a sign bit, in four 7-bit data bytes.
+pub(crate) fn encode_float(value: f32) -> [u8; 4] {
+    let mut significand = (value as f64).abs();
+    let mut exponent = 0i32;
+    if significand > 0.0 {
+        while significand >= MAX_SIGNIFICAND && exponent < 4 {
+            significand /= 10.0;
+            exponent += 1;
+        }
+        while significand.fract() != 0.0 && significand * 10.0 < MAX_SIGNIFICAND && exponent > -11 {
+            significand *= 10.0;
+            exponent -= 1;
+        }
+    }
+    let significand = (significand.round() as u32).min(MAX_SIGNIFICAND as u32 - 1);
+    let exponent = (exponent + 11) as u8;
+    [
+        significand as u8 & 0x7F,
+        (significand >> 7) as u8 & 0x7F,
+        (significand >> 14) as u8 & 0x7F,
+        (significand >> 21) as u8 & 0x03 | (exponent & 0x0F) << 2 | ((value < 0.0) as u8) << 6,
+    ]
+}
+
+/// Decode four 7-bit data bytes encoded with [`encode_float`].
+pub(crate) fn decode_float(data: &[u8; 4]) -> f32 {
+    let significand = data[0] as u32 & 0x7F
+        | (data[1] as u32 & 0x7F) << 7
+        | (data[2] as u32 & 0x7F) << 14
+        | (data[3] as u32 & 0x03) << 21;
+    let exponent = ((data[3] >> 2) & 0x0F) as i32 - 11;
+    let value = (significand as f64 * 10f64.powi(exponent)) as f32;
+    match data[3] & 0x40 {
+        0 => value,
+        _ => -value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_captured_stream() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[
+            0xF9, 0x02, 0x05, // protocol version
+            0xE1, 0x2C, 0x02, // analog channel 1
+            0x90, 0x05, 0x00, // digital port 0
+            0xF0, 0x79, 0x02, 0x05, 0xF7, // firmware without name
+            0xF0, 0x6A, 0x7F, 0x00, 0xF7, // analog mapping
+        ]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::ProtocolVersion(ProtocolVersion::new(2, 5)))
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::Analog {
+                channel: 1,
+                value: 300
+            })
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::Digital { port: 0, mask: 5 })
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::ReportFirmware {
+                version: FirmwareVersion::new(2, 5),
+                name: String::new()
+            })
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::AnalogMappingResponse(vec![None, Some(0)]))
+        );
+        assert_eq!(decoder.decode().unwrap(), None);
+        assert_eq!(decoder.skipped(), 0);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn decode_firmware_name() {
+        let mut decoder = Decoder::new();
+        let mut frame = vec![0xF0, 0x79, 0x02, 0x05];
+        for byte in b"StandardFirmata.ino" {
+            frame.extend([byte & 0x7F, byte >> 7]);
+        }
+        frame.push(0xF7);
+        decoder.feed(&frame);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::ReportFirmware {
+                version: FirmwareVersion::new(2, 5),
+                name: "StandardFirmata.ino".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn skip_leading_garbage() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0x01, 0x02, 0x7F, 0x91, 0x25, 0x01]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::Digital {
+                port: 1,
+                mask: 0xA5
+            })
+        );
+        assert_eq!(decoder.skipped(), 3);
+    }
+
+    #[test]
+    fn skip_message_cut_short_by_command() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xE3, 0x48, 0x91, 0x25, 0x01]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::Digital {
+                port: 1,
+                mask: 0xA5
+            })
+        );
+        assert_eq!(decoder.skipped(), 2);
+    }
+
+    #[test]
+    fn skip_sysex_cut_short_by_sysex() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x79, 0x02, 0xF0, 0x6E, 0x05, 0xF7]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::PinStateResponse {
+                pin: 5,
+                mode: None,
+                value: 0
+            })
+        );
+        assert_eq!(decoder.skipped(), 3);
+    }
+
+    #[test]
+    fn skip_unknown_command() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF7, 0xE3, 0x48, 0x01]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::Analog {
+                channel: 3,
+                value: 200
+            })
+        );
+        assert_eq!(decoder.skipped(), 1);
+    }
+
+    #[test]
+    fn continue_after_error() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x01, 0xF7, 0xE3, 0x48, 0x01]);
+        assert!(matches!(
+            decoder.decode(),
+            Err(Error::UnknownSysEx { code: 0x01 })
+        ));
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::Analog {
+                channel: 3,
+                value: 200
+            })
+        );
+        assert_eq!(decoder.decode().unwrap(), None);
+    }
+
+    #[test]
+    fn decode_pin_state_response() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x6E, 0x0D, 0x03, 0x7F, 0x07, 0xF7]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::PinStateResponse {
+                pin: 13,
+                mode: Some(PinMode::Pwm),
+                value: 0x3FF
+            })
+        );
+    }
+
+    #[test]
+    fn empty_pin_state_response() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x6E, 0xF7]);
+        assert!(matches!(decoder.decode(), Err(Error::MessageTooShort)));
+    }
+
+    #[test]
+    fn decode_string_data() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x71, 0x4F, 0x00, 0x4B, 0x00, 0x21, 0x00, 0xF7]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::StringData("OK!".to_string()))
+        );
+    }
+
+    #[test]
+    fn decode_capability_response() {
+        let mut decoder = Decoder::new();
+        // Pin 0 supports no modes, pin 1 is digital and PWM and pin 2 analog.
+        decoder.feed(&[
+            0xF0, 0x6C, 0x7F, 0x00, 0x01, 0x03, 0x08, 0x7F, 0x02, 0x0A, 0x7F, 0xF7,
+        ]);
+        let Some(Message::CapabilityResponse(capabilities)) = decoder.decode().unwrap() else {
+            panic!("expected a capability response");
+        };
+        assert_eq!(capabilities.len(), 3);
+        assert!(capabilities.modes(0).is_empty());
+        assert_eq!(capabilities.resolution(1, PinMode::Input), Some(1));
+        assert_eq!(capabilities.resolution(1, PinMode::Pwm), Some(8));
+        assert_eq!(capabilities.resolution(2, PinMode::Analog), Some(10));
+        assert!(!capabilities.supports(2, PinMode::Pwm));
+        assert_eq!(
+            capabilities
+                .pins_supporting(PinMode::Pwm)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+    }
+
+    #[test]
+    fn decode_encoder_positions() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[
+            0xF0, 0x61, 0x00, 0x05, 0x00, 0x00, 0x00, // Encoder 0 at 5.
+            0x41, 0x2C, 0x02, 0x00, 0x00, 0xF7, // Encoder 1 at -300.
+        ]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::EncoderPositions(vec![
+                EncoderPosition {
+                    encoder: 0,
+                    position: 5
+                },
+                EncoderPosition {
+                    encoder: 1,
+                    position: -300
+                },
+            ]))
+        );
+    }
+
+    #[test]
+    fn decode_accel_stepper_replies() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x62, 0x0A, 0x01, 0x68, 0x07, 0x00, 0x00, 0x08, 0xF7]);
+        decoder.feed(&[0xF0, 0x62, 0x24, 0x00, 0xF7]);
+        decoder.feed(&[0xF0, 0x62, 0x06, 0x01, 0x68, 0xF7]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::AccelStepperMoveComplete {
+                device: 1,
+                position: -1000
+            })
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::MultiStepperMoveComplete { group: 0 })
+        );
+        assert!(matches!(decoder.decode(), Err(Error::MessageTooShort)));
+    }
+
+    #[test]
+    fn decode_scheduler_replies() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x7B, 0x09, 0x00, 0x01, 0xF7]);
+        // Task 0 runs at 1000 ms with 2 of its 4 bytes done.
+        decoder.feed(&[
+            0xF0, 0x7B, 0x0A, 0x00, 0x68, 0x07, 0x00, 0x00, 0x40, 0x00, 0x00, 0x01, 0x00, 0x54,
+            0x6E, 0x65, 0x5C, 0x1B, 0xF7,
+        ]);
+        decoder.feed(&[0xF0, 0x7B, 0x08, 0x01, 0xF7]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::SchedulerTasks(vec![0, 1]))
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::SchedulerTaskReply {
+                id: 0,
+                task: Some(SchedulerTask {
+                    time: 1000,
+                    length: 4,
+                    position: 2,
+                    data: vec![0xAA, 0xBB, 0xCC, 0xDD]
+                })
+            })
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::SchedulerTaskError { id: 1, task: None })
+        );
+        assert_eq!(SchedulerTask::decode(&[0; 7]), None);
+    }
+
+    #[test]
+    fn decode_shift_in_reply() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x75, 0x03, 0x04, 0x25, 0x01, 0x7F, 0x00, 0xF7]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::ShiftInReply {
+                data_pin: 4,
+                data: vec![0xA5, 0x7F]
+            })
+        );
+    }
+
+    #[test]
+    fn decode_spi_reply() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[
+            0xF0, 0x68, 0x05, 0x08, 0x03, 0x02, 0x25, 0x01, 0x7F, 0x00, 0xF7,
+        ]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::SpiReply {
+                device: SpiDevice::new(0, 1),
+                request_id: 3,
+                data: vec![0xA5, 0x7F]
+            })
+        );
+    }
+
+    #[test]
+    fn decode_onewire_replies() {
+        let mut decoder = Decoder::new();
+        // A device with a valid CRC, then the same device with a corrupted CRC.
+        decoder.feed(&[
+            0xF0, 0x73, 0x42, 0x02, 0x02, 0x38, 0x60, 0x0D, 0x00, 0x00, 0x00, 0x00, 0x22, 0x05,
+            0x70, 0x40, 0x1B, 0x00, 0x00, 0x00, 0x00, 0x46, 0x02, 0xF7,
+        ]);
+        // Correlation ID 0x1234, then the bytes read.
+        decoder.feed(&[0xF0, 0x73, 0x43, 0x02, 0x34, 0x24, 0x28, 0x5D, 0x0B, 0xF7]);
+        let address = OneWireAddress::new([0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xA2]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::OneWireSearchReply {
+                pin: 2,
+                alarms: false,
+                addresses: vec![address.unwrap()]
+            })
+        );
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::OneWireReadReply {
+                pin: 2,
+                correlation_id: 0x1234,
+                data: vec![0xAA, 0xBB]
+            })
+        );
+    }
+
+    #[test]
+    fn packed_round_trip() {
+        let data: Vec<u8> = (0..=255).collect();
+        let packed = encode_packed(&data);
+        assert!(packed.iter().all(|&byte| byte <= SYSEX_REALTIME));
+        assert_eq!(decode_packed(&packed), data);
+        assert_eq!(encode_packed(&[0xFF]), [0x7F, 0x01]);
+    }
+
+    #[test]
+    fn decode_stepper_move_complete() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x72, 0x01, 0xF7, 0xF0, 0x72, 0xF7]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::StepperMoveComplete { device: 1 })
+        );
+        assert!(matches!(decoder.decode(), Err(Error::MessageTooShort)));
+    }
+
+    #[test]
+    fn i32_round_trip() {
+        for value in [0, 1, -1, 1000, -1000, i32::MAX, -i32::MAX] {
+            assert_eq!(decode_i32(&encode_i32(value)), value);
+        }
+        assert_eq!(decode_i32(&encode_i32(i32::MIN)), -i32::MAX);
+    }
+
+    #[test]
+    fn float_round_trip() {
+        for value in [0.0, 0.5, 100.0, -3.25, 1234.5] {
+            assert_eq!(decode_float(&encode_float(value)), value);
+        }
+    }
+
+    #[test]
+    fn decode_split_feeds() {
+        let mut decoder = Decoder::new();
+        decoder.feed(&[0xF0, 0x6C, 0x00]);
+        assert_eq!(decoder.decode().unwrap(), None);
+        decoder.feed(&[0x01, 0x7F, 0xF7, 0xE0]);
+        assert_eq!(
+            decoder.decode().unwrap(),
+            Some(Message::CapabilityResponse(Capabilities {
+                pins: vec![vec![ModeCapability {
+                    mode: PinMode::Input,
+                    resolution: 1
+                }]]
+            }))
+        );
+        assert_eq!(decoder.decode().unwrap(), None);
+        assert_eq!(decoder.skipped(), 0);
+        assert_eq!(decoder.buffered(), 1);
+    }
+}
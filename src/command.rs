@@ -0,0 +1,1807 @@
+//! Typed Firmata commands sent from the host to the board.
+
+use crate::*;
+
+/// A Firmata command as sent from the host to a Firmata device.
+///
+/// Use [`Command::encode`] to get the bytes to send and [`Command::decode`] to turn a frame of
+/// bytes back into a `Command`, e.g. to log or replay captured traffic.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    /// Write a 14-bit `value` to an analog (or PWM) `pin`.
+    AnalogWrite { pin: u8, value: u16 },
+    /// Write a `value` of any resolution to any analog (or PWM) `pin`.
+    ExtendedAnalog { pin: u8, value: u32 },
+    /// Write the levels of all pins in a digital `port` as a bitmask.
+    DigitalWrite { port: u8, mask: u8 },
+    /// Enable or disable analog reporting of a `channel`.
+    ReportAnalog { channel: u8, enable: bool },
+    /// Enable or disable digital reporting of a `port`.
+    ReportDigital { port: u8, enable: bool },
+    /// Set the `mode` of a `pin`.
+    SetPinMode { pin: u8, mode: PinMode },
+    /// Set the level of a single digital `pin`.
+    SetDigitalPinValue { pin: u8, value: bool },
+    /// Ask for the protocol version.
+    ReportVersion,
+    /// Reset the board to its initial state.
+    SystemReset,
+    /// Ask for the mapping of analog channels to pin numbers.
+    AnalogMappingQuery,
+    /// Ask for the supported modes and resolutions of all pins.
+    CapabilityQuery,
+    /// Ask for the firmware name and version.
+    ReportFirmware,
+    /// Ask for the current mode and value of a `pin`.
+    PinStateQuery { pin: u8 },
+    /// Set the `interval` in milliseconds at which analog and I2C data is sampled and reported.
+    SamplingInterval { interval: u16 },
+    /// Configure the `delay` in microseconds between an I2C register write and its read.
+    I2CConfig { delay: u16 },
+    /// Read from, write to or stop reading from an I2C device.
+    I2CRequest(I2CRequest),
+    /// Attach a servo to `pin` with the pulse widths in microseconds at 0 and 180 degrees.
+    ServoConfig {
+        pin: u8,
+        min_pulse: u16,
+        max_pulse: u16,
+    },
+    /// Configure AccelStepper `device`.
+    AccelStepperConfig {
+        device: u8,
+        config: AccelStepperConfig,
+    },
+    /// Set the current position of AccelStepper `device` to zero.
+    AccelStepperZero { device: u8 },
+    /// Move AccelStepper `device` a number of `steps`, backwards if negative.
+    AccelStepperStep { device: u8, steps: i32 },
+    /// Move AccelStepper `device` to an absolute `position`.
+    AccelStepperTo { device: u8, position: i32 },
+    /// Enable or disable the driver of AccelStepper `device`.
+    AccelStepperEnable { device: u8, enable: bool },
+    /// Stop AccelStepper `device`.
+    AccelStepperStop { device: u8 },
+    /// Ask for the position of AccelStepper `device`.
+    AccelStepperReportPosition { device: u8 },
+    /// Set the `acceleration` of AccelStepper `device` in steps/s².
+    AccelStepperSetAcceleration { device: u8, acceleration: f32 },
+    /// Set the `speed` of AccelStepper `device` in steps/s.
+    AccelStepperSetSpeed { device: u8, speed: f32 },
+    /// Group AccelStepper `devices` to move them in sync.
+    MultiStepperConfig { group: u8, devices: Vec<u8> },
+    /// Move the steppers of a `group` to absolute `positions`, in the order of the group.
+    MultiStepperTo { group: u8, positions: Vec<i32> },
+    /// Stop the steppers of a `group`.
+    MultiStepperStop { group: u8 },
+    /// Attach `encoder` to the pins of its A and B channels.
+    EncoderAttach { encoder: u8, pin_a: u8, pin_b: u8 },
+    /// Ask for the position of `encoder`.
+    EncoderReportPosition { encoder: u8 },
+    /// Ask for the positions of all encoders.
+    EncoderReportPositions,
+    /// Reset the position of `encoder` to zero.
+    EncoderResetPosition { encoder: u8 },
+    /// Enable or disable reporting the positions of all encoders at every sampling interval.
+    EncoderReportAuto { enable: bool },
+    /// Detach `encoder`.
+    EncoderDetach { encoder: u8 },
+    /// Configure `pin` as a OneWire bus, with parasitic `power` after writes if set.
+    OneWireConfig { pin: u8, power: bool },
+    /// Search for the devices on the OneWire bus at `pin`.
+    OneWireSearch { pin: u8 },
+    /// Search for the devices in an alarm state on the OneWire bus at `pin`.
+    OneWireSearchAlarms { pin: u8 },
+    /// Run a `request` on the OneWire bus at `pin`.
+    OneWireRequest { pin: u8, request: OneWireRequest },
+    /// Create scheduler `task` with a body of `length` bytes.
+    SchedulerCreateTask { task: u8, length: u16 },
+    /// Delete scheduler `task`.
+    SchedulerDeleteTask { task: u8 },
+    /// Append encoded commands in `data` to the body of scheduler `task`.
+    SchedulerAddToTask { task: u8, data: Vec<u8> },
+    /// Delay the running task by `delay` milliseconds, only used in task bodies.
+    SchedulerDelayTask { delay: u32 },
+    /// Run scheduler `task` in `delay` milliseconds.
+    SchedulerScheduleTask { task: u8, delay: u32 },
+    /// Ask for the IDs of all scheduler tasks.
+    SchedulerQueryAllTasks,
+    /// Ask for the state of scheduler `task`.
+    SchedulerQueryTask { task: u8 },
+    /// Delete all scheduler tasks.
+    SchedulerReset,
+    /// Configure a serial `port` with a `baud` rate, and the RX and TX `pins` of software ports.
+    SerialConfig {
+        port: SerialPortId,
+        baud: u32,
+        pins: Option<(u8, u8)>,
+    },
+    /// Write `data` to a serial `port`.
+    SerialWrite { port: SerialPortId, data: Vec<u8> },
+    /// Read continuously from a serial `port`, at most `max_bytes` per report if set.
+    SerialRead {
+        port: SerialPortId,
+        max_bytes: Option<u16>,
+    },
+    /// Stop reading from a serial `port`.
+    SerialStopReading { port: SerialPortId },
+    /// Close a serial `port`.
+    SerialClose { port: SerialPortId },
+    /// Flush a serial `port`.
+    SerialFlush { port: SerialPortId },
+    /// Listen on a software serial `port`.
+    SerialListen { port: SerialPortId },
+    /// Shift `data` out of `data_pin`, clocked by `clock_pin`.
+    ShiftOut {
+        data_pin: u8,
+        clock_pin: u8,
+        bit_order: BitOrder,
+        data: Vec<u8>,
+    },
+    /// Shift `size` bytes into `data_pin`, clocked by `clock_pin`.
+    ShiftIn {
+        data_pin: u8,
+        clock_pin: u8,
+        bit_order: BitOrder,
+        size: u8,
+    },
+    /// Initialize SPI `channel`.
+    SpiBegin { channel: u8 },
+    /// Configure SPI `device`.
+    SpiDeviceConfig {
+        device: SpiDevice,
+        config: SpiDeviceConfig,
+    },
+    /// Write `data` to SPI `device` and read as many words back, replied with `request_id`.
+    SpiTransfer {
+        device: SpiDevice,
+        request_id: u8,
+        deselect: bool,
+        data: Vec<u8>,
+    },
+    /// Write `data` to SPI `device`.
+    SpiWrite {
+        device: SpiDevice,
+        request_id: u8,
+        deselect: bool,
+        data: Vec<u8>,
+    },
+    /// Read `size` words from SPI `device`, replied with `request_id`.
+    SpiRead {
+        device: SpiDevice,
+        request_id: u8,
+        deselect: bool,
+        size: u8,
+    },
+    /// Release SPI `channel`.
+    SpiEnd { channel: u8 },
+    /// Configure stepper `device` with the number of steps per revolution and its wiring.
+    StepperConfig {
+        device: u8,
+        steps_per_revolution: u16,
+        interface: StepperInterface,
+    },
+    /// Move stepper `device` a number of `steps` at `speed` in 0.01 rad/s, with an optional
+    /// acceleration and deceleration `ramp`.
+    StepperStep {
+        device: u8,
+        direction: StepperDirection,
+        steps: u32,
+        speed: u16,
+        ramp: Option<StepperRamp>,
+    },
+    /// Send a string to firmware that accepts text commands.
+    StringData(String),
+}
+
+impl Command {
+    /// Encode the command into the bytes to send to the board.
+    ///
+    /// Fails with [`Error::InvalidCommandField`] if a field does not fit in the bits the protocol
+    /// has for it, such as a pin above 127 or an analog pin above 15.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        Ok(match self {
+            Command::AnalogWrite { pin, value } => {
+                let [lsb, msb] = encode_u14(field("value", *value, 0x3FFF)?);
+                vec![ANALOG_MESSAGE | field("pin", *pin, 0x0F)?, lsb, msb]
+            }
+            Command::ExtendedAnalog { pin, value } => {
+                let mut buf = vec![START_SYSEX, EXTENDED_ANALOG, field("pin", *pin, 0x7F)?];
+                buf.extend(encode_u32(*value));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::DigitalWrite { port, mask } => {
+                let [lsb, msb] = encode_u14(*mask as u16);
+                vec![DIGITAL_MESSAGE | field("port", *port, 0x0F)?, lsb, msb]
+            }
+            Command::ReportAnalog { channel, enable } => {
+                vec![
+                    REPORT_ANALOG | field("channel", *channel, 0x0F)?,
+                    *enable as u8,
+                ]
+            }
+            Command::ReportDigital { port, enable } => {
+                vec![REPORT_DIGITAL | field("port", *port, 0x0F)?, *enable as u8]
+            }
+            Command::SetPinMode { pin, mode } => vec![
+                SET_PIN_MODE,
+                field("pin", *pin, 0x7F)?,
+                field("mode", u8::from(*mode), 0x7F)?,
+            ],
+            Command::SetDigitalPinValue { pin, value } => {
+                vec![
+                    SET_DIGITAL_PIN_VALUE,
+                    field("pin", *pin, 0x7F)?,
+                    *value as u8,
+                ]
+            }
+            Command::ReportVersion => vec![REPORT_VERSION],
+            Command::SystemReset => vec![SYSTEM_RESET],
+            Command::AnalogMappingQuery => vec![START_SYSEX, ANALOG_MAPPING_QUERY, END_SYSEX],
+            Command::CapabilityQuery => vec![START_SYSEX, CAPABILITY_QUERY, END_SYSEX],
+            Command::ReportFirmware => vec![START_SYSEX, REPORT_FIRMWARE, END_SYSEX],
+            Command::PinStateQuery { pin } => vec![
+                START_SYSEX,
+                PIN_STATE_QUERY,
+                field("pin", *pin, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::SamplingInterval { interval } => {
+                let [lsb, msb] = encode_u14(field("interval", *interval, 0x3FFF)?);
+                vec![START_SYSEX, SAMPLING_INTERVAL, lsb, msb, END_SYSEX]
+            }
+            Command::I2CConfig { delay } => {
+                let [lsb, msb] = encode_u14(field("delay", *delay, 0x3FFF)?);
+                vec![START_SYSEX, I2C_CONFIG, lsb, msb, END_SYSEX]
+            }
+            Command::I2CRequest(request) => {
+                let max = if request.ten_bit { 0x3FF } else { 0x7F };
+                let address = field("address", request.address, max)?;
+                let mut flags = (address >> 7) as u8;
+                if request.ten_bit {
+                    flags |= I2C_10BIT_ADDRESS_MODE_MASK;
+                }
+                if request.auto_restart {
+                    flags |= I2C_END_TX_MASK;
+                }
+                let mut buf = vec![START_SYSEX, I2C_REQUEST, address as u8 & SYSEX_REALTIME];
+                match &request.operation {
+                    I2COperation::Write { data } => {
+                        buf.push(flags | I2C_WRITE);
+                        buf.extend(encode_bytes(data));
+                    }
+                    I2COperation::Read { register, size }
+                    | I2COperation::ReadContinuously { register, size } => {
+                        let mode = match request.operation {
+                            I2COperation::Read { .. } => I2C_MODE_READ,
+                            _ => I2C_READ_CONTINUOUSLY,
+                        };
+                        buf.push(flags | mode);
+                        if let Some(register) = register {
+                            buf.extend(encode_u14(field("register", *register, 0x3FFF)?));
+                        }
+                        buf.extend(encode_u14(field("size", *size, 0x3FFF)?));
+                    }
+                    I2COperation::StopReading => buf.push(flags | I2C_STOP_READING),
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::ServoConfig {
+                pin,
+                min_pulse,
+                max_pulse,
+            } => {
+                let [min_lsb, min_msb] = encode_u14(field("min_pulse", *min_pulse, 0x3FFF)?);
+                let [max_lsb, max_msb] = encode_u14(field("max_pulse", *max_pulse, 0x3FFF)?);
+                vec![
+                    START_SYSEX,
+                    SERVO_CONFIG,
+                    field("pin", *pin, 0x7F)?,
+                    min_lsb,
+                    min_msb,
+                    max_lsb,
+                    max_msb,
+                    END_SYSEX,
+                ]
+            }
+            Command::AccelStepperConfig { device, config } => {
+                let wires = match config.interface {
+                    AccelStepperInterface::Driver { .. } => 1,
+                    AccelStepperInterface::TwoWire { .. } => 2,
+                    AccelStepperInterface::ThreeWire { .. } => 3,
+                    AccelStepperInterface::FourWire { .. } => 4,
+                };
+                let step_size = match config.step_size {
+                    StepSize::Whole => 0,
+                    StepSize::Half => 1,
+                    StepSize::Quarter => 2,
+                };
+                let interface = wires << 4 | step_size << 1 | config.enable_pin.is_some() as u8;
+                let mut buf = vec![
+                    START_SYSEX,
+                    ACCELSTEPPER_DATA,
+                    ACCELSTEPPER_CONFIG,
+                    field("device", *device, 0x7F)?,
+                    interface,
+                ];
+                for pin in config.interface.pins().into_iter().chain(config.enable_pin) {
+                    buf.push(field("pin", pin, 0x7F)?);
+                }
+                buf.push(field("invert", config.invert, 0x1F)?);
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::AccelStepperZero { device } => vec![
+                START_SYSEX,
+                ACCELSTEPPER_DATA,
+                ACCELSTEPPER_ZERO,
+                field("device", *device, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::AccelStepperStep { device, steps } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    ACCELSTEPPER_DATA,
+                    ACCELSTEPPER_STEP,
+                    field("device", *device, 0x7F)?,
+                ];
+                buf.extend(encode_i32(*steps));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::AccelStepperTo { device, position } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    ACCELSTEPPER_DATA,
+                    ACCELSTEPPER_TO,
+                    field("device", *device, 0x7F)?,
+                ];
+                buf.extend(encode_i32(*position));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::AccelStepperEnable { device, enable } => vec![
+                START_SYSEX,
+                ACCELSTEPPER_DATA,
+                ACCELSTEPPER_ENABLE,
+                field("device", *device, 0x7F)?,
+                *enable as u8,
+                END_SYSEX,
+            ],
+            Command::AccelStepperStop { device } => vec![
+                START_SYSEX,
+                ACCELSTEPPER_DATA,
+                ACCELSTEPPER_STOP,
+                field("device", *device, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::AccelStepperReportPosition { device } => vec![
+                START_SYSEX,
+                ACCELSTEPPER_DATA,
+                ACCELSTEPPER_REPORT_POSITION,
+                field("device", *device, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::AccelStepperSetAcceleration {
+                device,
+                acceleration,
+            } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    ACCELSTEPPER_DATA,
+                    ACCELSTEPPER_SET_ACCELERATION,
+                    field("device", *device, 0x7F)?,
+                ];
+                buf.extend(encode_float(*acceleration));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::AccelStepperSetSpeed { device, speed } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    ACCELSTEPPER_DATA,
+                    ACCELSTEPPER_SET_SPEED,
+                    field("device", *device, 0x7F)?,
+                ];
+                buf.extend(encode_float(*speed));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::MultiStepperConfig { group, devices } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    ACCELSTEPPER_DATA,
+                    MULTISTEPPER_CONFIG,
+                    field("group", field("group", *group, 0x7F)?, 0x7F)?,
+                ];
+                for device in devices {
+                    buf.push(field("device", *device, 0x7F)?);
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::MultiStepperTo { group, positions } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    ACCELSTEPPER_DATA,
+                    MULTISTEPPER_TO,
+                    field("group", field("group", *group, 0x7F)?, 0x7F)?,
+                ];
+                for position in positions {
+                    buf.extend(encode_i32(*position));
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::MultiStepperStop { group } => vec![
+                START_SYSEX,
+                ACCELSTEPPER_DATA,
+                MULTISTEPPER_STOP,
+                field("group", *group, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::EncoderAttach {
+                encoder,
+                pin_a,
+                pin_b,
+            } => vec![
+                START_SYSEX,
+                ENCODER_DATA,
+                ENCODER_ATTACH,
+                field("encoder", *encoder, ENCODER_CHANNEL_MASK.into())?,
+                field("pin_a", *pin_a, 0x7F)?,
+                field("pin_b", *pin_b, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::EncoderReportPosition { encoder } => vec![
+                START_SYSEX,
+                ENCODER_DATA,
+                ENCODER_REPORT_POSITION,
+                field("encoder", *encoder, ENCODER_CHANNEL_MASK.into())?,
+                END_SYSEX,
+            ],
+            Command::EncoderReportPositions => {
+                vec![
+                    START_SYSEX,
+                    ENCODER_DATA,
+                    ENCODER_REPORT_POSITIONS,
+                    END_SYSEX,
+                ]
+            }
+            Command::EncoderResetPosition { encoder } => vec![
+                START_SYSEX,
+                ENCODER_DATA,
+                ENCODER_RESET_POSITION,
+                field("encoder", *encoder, ENCODER_CHANNEL_MASK.into())?,
+                END_SYSEX,
+            ],
+            Command::EncoderReportAuto { enable } => vec![
+                START_SYSEX,
+                ENCODER_DATA,
+                ENCODER_REPORT_AUTO,
+                *enable as u8,
+                END_SYSEX,
+            ],
+            Command::EncoderDetach { encoder } => {
+                vec![
+                    START_SYSEX,
+                    ENCODER_DATA,
+                    ENCODER_DETACH,
+                    field("encoder", *encoder, ENCODER_CHANNEL_MASK.into())?,
+                    END_SYSEX,
+                ]
+            }
+            Command::OneWireConfig { pin, power } => vec![
+                START_SYSEX,
+                ONEWIRE_DATA,
+                ONEWIRE_CONFIG_REQUEST,
+                field("pin", *pin, 0x7F)?,
+                *power as u8,
+                END_SYSEX,
+            ],
+            Command::OneWireSearch { pin } => {
+                vec![
+                    START_SYSEX,
+                    ONEWIRE_DATA,
+                    ONEWIRE_SEARCH_REQUEST,
+                    field("pin", *pin, 0x7F)?,
+                    END_SYSEX,
+                ]
+            }
+            Command::OneWireSearchAlarms { pin } => vec![
+                START_SYSEX,
+                ONEWIRE_DATA,
+                ONEWIRE_SEARCH_ALARMS_REQUEST,
+                field("pin", *pin, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::OneWireRequest { pin, request } => {
+                let mut bits = 0;
+                // The firmware reads every field at a fixed offset, whether the request uses it or
+                // not: address at 0, read size at 8, correlation ID at 10, delay at 12 and the
+                // bytes to write from 16.
+                let mut data = vec![0; ONEWIRE_REQUEST_HEADER_BYTES];
+                if request.reset {
+                    bits |= ONEWIRE_RESET_REQUEST_BIT;
+                }
+                if request.skip {
+                    bits |= ONEWIRE_SKIP_REQUEST_BIT;
+                }
+                if let Some(address) = request.select {
+                    bits |= ONEWIRE_SELECT_REQUEST_BIT;
+                    data[0..8].copy_from_slice(address.as_bytes());
+                }
+                if let Some((size, correlation_id)) = request.read {
+                    bits |= ONEWIRE_READ_REQUEST_BIT;
+                    data[8..10].copy_from_slice(&size.to_le_bytes());
+                    data[10..12].copy_from_slice(&correlation_id.to_le_bytes());
+                }
+                if let Some(delay) = request.delay {
+                    bits |= ONEWIRE_DELAY_REQUEST_BIT;
+                    data[12..16].copy_from_slice(&delay.to_le_bytes());
+                }
+                if let Some(write) = &request.write {
+                    bits |= ONEWIRE_WRITE_REQUEST_BIT;
+                    data.extend(write);
+                }
+                let mut buf = vec![START_SYSEX, ONEWIRE_DATA, bits, field("pin", *pin, 0x7F)?];
+                buf.extend(encode_packed(&data));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SchedulerCreateTask { task, length } => {
+                let [lsb, msb] = encode_u14(field("length", *length, 0x3FFF)?);
+                vec![
+                    START_SYSEX,
+                    SCHEDULER_DATA,
+                    SCHEDULER_CREATE_TASK,
+                    field("task", *task, 0x7F)?,
+                    lsb,
+                    msb,
+                    END_SYSEX,
+                ]
+            }
+            Command::SchedulerDeleteTask { task } => vec![
+                START_SYSEX,
+                SCHEDULER_DATA,
+                SCHEDULER_DELETE_TASK,
+                field("task", *task, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::SchedulerAddToTask { task, data } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    SCHEDULER_DATA,
+                    SCHEDULER_ADD_TO_TASK,
+                    field("task", *task, 0x7F)?,
+                ];
+                buf.extend(encode_packed(data));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SchedulerDelayTask { delay } => {
+                let mut buf = vec![START_SYSEX, SCHEDULER_DATA, SCHEDULER_DELAY_TASK];
+                buf.extend(encode_packed(&delay.to_le_bytes()));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SchedulerScheduleTask { task, delay } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    SCHEDULER_DATA,
+                    SCHEDULER_SCHEDULE_TASK,
+                    field("task", *task, 0x7F)?,
+                ];
+                buf.extend(encode_packed(&delay.to_le_bytes()));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SchedulerQueryAllTasks => vec![
+                START_SYSEX,
+                SCHEDULER_DATA,
+                SCHEDULER_QUERY_ALL_TASKS,
+                END_SYSEX,
+            ],
+            Command::SchedulerQueryTask { task } => vec![
+                START_SYSEX,
+                SCHEDULER_DATA,
+                SCHEDULER_QUERY_TASK,
+                field("task", *task, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::SchedulerReset => {
+                vec![START_SYSEX, SCHEDULER_DATA, SCHEDULER_RESET, END_SYSEX]
+            }
+            Command::SerialConfig { port, baud, pins } => {
+                let baud = field("baud", *baud, 0x1F_FFFF)?;
+                let mut buf = vec![
+                    START_SYSEX,
+                    SERIAL_DATA,
+                    SERIAL_CONFIG | serial_port(*port)?,
+                    (baud & 0x7F) as u8,
+                    ((baud >> 7) & 0x7F) as u8,
+                    ((baud >> 14) & 0x7F) as u8,
+                ];
+                if let Some((rx, tx)) = pins {
+                    buf.extend([field("rx", *rx, 0x7F)?, field("tx", *tx, 0x7F)?]);
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SerialWrite { port, data } => {
+                let mut buf = vec![START_SYSEX, SERIAL_DATA, SERIAL_WRITE | serial_port(*port)?];
+                buf.extend(encode_bytes(data));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SerialRead { port, max_bytes } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    SERIAL_DATA,
+                    SERIAL_READ | serial_port(*port)?,
+                    SERIAL_READ_CONTINUOUSLY,
+                ];
+                if let Some(max_bytes) = max_bytes {
+                    buf.extend(encode_u14(field("max_bytes", *max_bytes, 0x3FFF)?));
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SerialStopReading { port } => vec![
+                START_SYSEX,
+                SERIAL_DATA,
+                SERIAL_READ | serial_port(*port)?,
+                SERIAL_STOP_READING,
+                END_SYSEX,
+            ],
+            Command::SerialClose { port } => {
+                vec![
+                    START_SYSEX,
+                    SERIAL_DATA,
+                    SERIAL_CLOSE | serial_port(*port)?,
+                    END_SYSEX,
+                ]
+            }
+            Command::SerialFlush { port } => {
+                vec![
+                    START_SYSEX,
+                    SERIAL_DATA,
+                    SERIAL_FLUSH | serial_port(*port)?,
+                    END_SYSEX,
+                ]
+            }
+            Command::SerialListen { port } => {
+                vec![
+                    START_SYSEX,
+                    SERIAL_DATA,
+                    SERIAL_LISTEN | serial_port(*port)?,
+                    END_SYSEX,
+                ]
+            }
+            Command::ShiftOut {
+                data_pin,
+                clock_pin,
+                bit_order,
+                data,
+            } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    SHIFT_DATA,
+                    SHIFT_OUT,
+                    field("data_pin", *data_pin, 0x7F)?,
+                    field("clock_pin", *clock_pin, 0x7F)?,
+                    u8::from(*bit_order),
+                ];
+                buf.extend(encode_bytes(data));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::ShiftIn {
+                data_pin,
+                clock_pin,
+                bit_order,
+                size,
+            } => vec![
+                START_SYSEX,
+                SHIFT_DATA,
+                SHIFT_IN,
+                field("data_pin", *data_pin, 0x7F)?,
+                field("clock_pin", *clock_pin, 0x7F)?,
+                u8::from(*bit_order),
+                field("size", *size, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::SpiBegin { channel } => {
+                vec![
+                    START_SYSEX,
+                    SPI_DATA,
+                    SPI_BEGIN,
+                    field("channel", *channel, 0x07)?,
+                    END_SYSEX,
+                ]
+            }
+            Command::SpiDeviceConfig { device, config } => {
+                let mut buf = vec![
+                    START_SYSEX,
+                    SPI_DATA,
+                    SPI_DEVICE_CONFIG,
+                    spi_device(*device)?,
+                    u8::from(config.mode) << 1 | u8::from(config.bit_order),
+                ];
+                buf.extend((0..5).map(|i| (config.max_speed >> (7 * i)) as u8 & SYSEX_REALTIME));
+                // Word size 0 is the default of 8 bits.
+                buf.push(0);
+                match config.cs_pin {
+                    Some(cs_pin) => {
+                        buf.push(0x01 | (config.cs_active_high as u8) << 1);
+                        buf.push(field("cs_pin", cs_pin, 0x7F)?);
+                    }
+                    None => buf.push(0),
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SpiTransfer {
+                device,
+                request_id,
+                deselect,
+                data,
+            }
+            | Command::SpiWrite {
+                device,
+                request_id,
+                deselect,
+                data,
+            } => {
+                let subcommand = match self {
+                    Command::SpiTransfer { .. } => SPI_TRANSFER,
+                    _ => SPI_WRITE,
+                };
+                let mut buf = vec![
+                    START_SYSEX,
+                    SPI_DATA,
+                    subcommand,
+                    spi_device(*device)?,
+                    field("request_id", *request_id, 0x7F)?,
+                    *deselect as u8,
+                    field("words", data.len().try_into().unwrap_or(u8::MAX), 0x7F)?,
+                ];
+                buf.extend(encode_bytes(data));
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::SpiRead {
+                device,
+                request_id,
+                deselect,
+                size,
+            } => vec![
+                START_SYSEX,
+                SPI_DATA,
+                SPI_READ,
+                spi_device(*device)?,
+                field("request_id", *request_id, 0x7F)?,
+                *deselect as u8,
+                field("size", *size, 0x7F)?,
+                END_SYSEX,
+            ],
+            Command::SpiEnd { channel } => {
+                vec![
+                    START_SYSEX,
+                    SPI_DATA,
+                    SPI_END,
+                    field("channel", *channel, 0x07)?,
+                    END_SYSEX,
+                ]
+            }
+            Command::StepperConfig {
+                device,
+                steps_per_revolution,
+                interface,
+            } => {
+                let kind = match interface {
+                    StepperInterface::Driver { .. } => STEPPER_DRIVER,
+                    StepperInterface::TwoWire { .. } => STEPPER_TWO_WIRE,
+                    StepperInterface::FourWire { .. } => STEPPER_FOUR_WIRE,
+                };
+                let mut buf = vec![
+                    START_SYSEX,
+                    STEPPER_DATA,
+                    STEPPER_CONFIG,
+                    field("device", *device, 0x7F)?,
+                    kind,
+                ];
+                buf.extend(encode_u14(field(
+                    "steps_per_revolution",
+                    *steps_per_revolution,
+                    0x3FFF,
+                )?));
+                for pin in interface.pins() {
+                    buf.push(field("pin", pin, 0x7F)?);
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::StepperStep {
+                device,
+                direction,
+                steps,
+                speed,
+                ramp,
+            } => {
+                let direction = match direction {
+                    StepperDirection::Clockwise => STEPPER_CW,
+                    StepperDirection::CounterClockwise => STEPPER_CCW,
+                };
+                let steps = field("steps", *steps, 0x1F_FFFF)?;
+                let mut buf = vec![
+                    START_SYSEX,
+                    STEPPER_DATA,
+                    STEPPER_STEP,
+                    field("device", *device, 0x7F)?,
+                    direction,
+                ];
+                buf.extend([
+                    (steps & 0x7F) as u8,
+                    ((steps >> 7) & 0x7F) as u8,
+                    ((steps >> 14) & 0x7F) as u8,
+                ]);
+                buf.extend(encode_u14(field("speed", *speed, 0x3FFF)?));
+                if let Some(ramp) = ramp {
+                    buf.extend(encode_u14(field(
+                        "acceleration",
+                        ramp.acceleration,
+                        0x3FFF,
+                    )?));
+                    buf.extend(encode_u14(field(
+                        "deceleration",
+                        ramp.deceleration,
+                        0x3FFF,
+                    )?));
+                }
+                buf.push(END_SYSEX);
+                buf
+            }
+            Command::StringData(string) => {
+                let mut buf = vec![START_SYSEX, STRING_DATA];
+                buf.extend(encode_bytes(string.as_bytes()));
+                buf.push(END_SYSEX);
+                buf
+            }
+        })
+    }
+
+    /// Decode a single complete command `frame`, as produced by [`Command::encode`].
+    pub fn decode(frame: &[u8]) -> Result<Command> {
+        let command = *frame.first().with_context(|| MessageTooShortSnafu)?;
+        let arg = |i: usize| frame.get(i).copied().with_context(|| MessageTooShortSnafu);
+        match command {
+            ANALOG_MESSAGE..=ANALOG_MESSAGE_BOUND => Ok(Command::AnalogWrite {
+                pin: command & 0x0F,
+                value: decode_u14(arg(1)?, arg(2)?),
+            }),
+            DIGITAL_MESSAGE..=DIGITAL_MESSAGE_BOUND => Ok(Command::DigitalWrite {
+                port: command & 0x0F,
+                mask: decode_u14(arg(1)?, arg(2)?) as u8,
+            }),
+            0xC0..=0xCF => Ok(Command::ReportAnalog {
+                channel: command & 0x0F,
+                enable: arg(1)? != 0,
+            }),
+            0xD0..=0xDF => Ok(Command::ReportDigital {
+                port: command & 0x0F,
+                enable: arg(1)? != 0,
+            }),
+            SET_PIN_MODE => Ok(Command::SetPinMode {
+                pin: arg(1)?,
+                mode: arg(2)?.try_into()?,
+            }),
+            SET_DIGITAL_PIN_VALUE => Ok(Command::SetDigitalPinValue {
+                pin: arg(1)?,
+                value: arg(2)? != 0,
+            }),
+            REPORT_VERSION => Ok(Command::ReportVersion),
+            SYSTEM_RESET => Ok(Command::SystemReset),
+            START_SYSEX => {
+                ensure!(
+                    frame.len() >= 3 && frame[frame.len() - 1] == END_SYSEX,
+                    MessageTooShortSnafu
+                );
+                let data = &frame[2..frame.len() - 1];
+                let arg = |i: usize| data.get(i).copied().with_context(|| MessageTooShortSnafu);
+                match frame[1] {
+                    ANALOG_MAPPING_QUERY => Ok(Command::AnalogMappingQuery),
+                    CAPABILITY_QUERY => Ok(Command::CapabilityQuery),
+                    REPORT_FIRMWARE => Ok(Command::ReportFirmware),
+                    PIN_STATE_QUERY => Ok(Command::PinStateQuery { pin: arg(0)? }),
+                    EXTENDED_ANALOG => Ok(Command::ExtendedAnalog {
+                        pin: arg(0)?,
+                        value: decode_u32(&data[1..]),
+                    }),
+                    SAMPLING_INTERVAL => Ok(Command::SamplingInterval {
+                        interval: decode_u14(arg(0)?, arg(1)?),
+                    }),
+                    I2C_CONFIG => Ok(Command::I2CConfig {
+                        delay: decode_u14(arg(0)?, arg(1)?),
+                    }),
+                    I2C_REQUEST => {
+                        let flags = arg(1)?;
+                        let args = &data[2..];
+                        let register = match args.len() {
+                            4.. => Some(decode_u14(args[0], args[1])),
+                            _ => None,
+                        };
+                        let size = || match args.len() {
+                            4.. => Ok(decode_u14(args[2], args[3])),
+                            2..=3 => Ok(decode_u14(args[0], args[1])),
+                            _ => Err(Error::MessageTooShort),
+                        };
+                        let operation = match flags & I2C_READ_WRITE_MODE_MASK {
+                            I2C_WRITE => I2COperation::Write {
+                                data: decode_bytes(args),
+                            },
+                            I2C_MODE_READ => I2COperation::Read {
+                                register,
+                                size: size()?,
+                            },
+                            I2C_READ_CONTINUOUSLY => I2COperation::ReadContinuously {
+                                register,
+                                size: size()?,
+                            },
+                            _ => I2COperation::StopReading,
+                        };
+                        Ok(Command::I2CRequest(I2CRequest {
+                            address: arg(0)? as u16 | ((flags & 0x07) as u16) << 7,
+                            ten_bit: flags & I2C_10BIT_ADDRESS_MODE_MASK != 0,
+                            auto_restart: flags & I2C_END_TX_MASK != 0,
+                            operation,
+                        }))
+                    }
+                    SERVO_CONFIG => Ok(Command::ServoConfig {
+                        pin: arg(0)?,
+                        min_pulse: decode_u14(arg(1)?, arg(2)?),
+                        max_pulse: decode_u14(arg(3)?, arg(4)?),
+                    }),
+                    ACCELSTEPPER_DATA => {
+                        let device = arg(1)?;
+                        let args = &data[2..];
+                        let position = || -> Result<i32> {
+                            let bytes = args.get(..5).with_context(|| MessageTooShortSnafu)?;
+                            Ok(decode_i32(bytes.try_into().expect("5 bytes")))
+                        };
+                        let float = || -> Result<f32> {
+                            let bytes = args.get(..4).with_context(|| MessageTooShortSnafu)?;
+                            Ok(decode_float(bytes.try_into().expect("4 bytes")))
+                        };
+                        match arg(0)? {
+                            ACCELSTEPPER_CONFIG => {
+                                let interface = arg(2)?;
+                                let pin = |i: usize| arg(3 + i);
+                                let (interface_pins, pins) = match interface >> 4 & 0x07 {
+                                    1 => (
+                                        AccelStepperInterface::Driver {
+                                            step_pin: pin(0)?,
+                                            direction_pin: pin(1)?,
+                                        },
+                                        2,
+                                    ),
+                                    2 => (
+                                        AccelStepperInterface::TwoWire {
+                                            pins: [pin(0)?, pin(1)?],
+                                        },
+                                        2,
+                                    ),
+                                    3 => (
+                                        AccelStepperInterface::ThreeWire {
+                                            pins: [pin(0)?, pin(1)?, pin(2)?],
+                                        },
+                                        3,
+                                    ),
+                                    _ => (
+                                        AccelStepperInterface::FourWire {
+                                            pins: [pin(0)?, pin(1)?, pin(2)?, pin(3)?],
+                                        },
+                                        4,
+                                    ),
+                                };
+                                let enable_pin = match interface & 0x01 {
+                                    0 => None,
+                                    _ => Some(pin(pins)?),
+                                };
+                                let invert = pin(pins + enable_pin.is_some() as usize).unwrap_or(0);
+                                Ok(Command::AccelStepperConfig {
+                                    device,
+                                    config: AccelStepperConfig {
+                                        interface: interface_pins,
+                                        step_size: match interface >> 1 & 0x07 {
+                                            0 => StepSize::Whole,
+                                            1 => StepSize::Half,
+                                            _ => StepSize::Quarter,
+                                        },
+                                        enable_pin,
+                                        invert,
+                                    },
+                                })
+                            }
+                            ACCELSTEPPER_ZERO => Ok(Command::AccelStepperZero { device }),
+                            ACCELSTEPPER_STEP => Ok(Command::AccelStepperStep {
+                                device,
+                                steps: position()?,
+                            }),
+                            ACCELSTEPPER_TO => Ok(Command::AccelStepperTo {
+                                device,
+                                position: position()?,
+                            }),
+                            ACCELSTEPPER_ENABLE => Ok(Command::AccelStepperEnable {
+                                device,
+                                enable: arg(2)? != 0,
+                            }),
+                            ACCELSTEPPER_STOP => Ok(Command::AccelStepperStop { device }),
+                            ACCELSTEPPER_REPORT_POSITION => {
+                                Ok(Command::AccelStepperReportPosition { device })
+                            }
+                            ACCELSTEPPER_SET_ACCELERATION => {
+                                Ok(Command::AccelStepperSetAcceleration {
+                                    device,
+                                    acceleration: float()?,
+                                })
+                            }
+                            ACCELSTEPPER_SET_SPEED => Ok(Command::AccelStepperSetSpeed {
+                                device,
+                                speed: float()?,
+                            }),
+                            MULTISTEPPER_CONFIG => Ok(Command::MultiStepperConfig {
+                                group: device,
+                                devices: args.to_vec(),
+                            }),
+                            MULTISTEPPER_TO => Ok(Command::MultiStepperTo {
+                                group: device,
+                                positions: args
+                                    .chunks_exact(5)
+                                    .map(|bytes| decode_i32(bytes.try_into().expect("5 bytes")))
+                                    .collect(),
+                            }),
+                            MULTISTEPPER_STOP => Ok(Command::MultiStepperStop { group: device }),
+                            _ => Err(Error::UnknownSysEx {
+                                code: ACCELSTEPPER_DATA,
+                            }),
+                        }
+                    }
+                    ENCODER_DATA => match arg(0)? {
+                        ENCODER_ATTACH => Ok(Command::EncoderAttach {
+                            encoder: arg(1)?,
+                            pin_a: arg(2)?,
+                            pin_b: arg(3)?,
+                        }),
+                        ENCODER_REPORT_POSITION => {
+                            Ok(Command::EncoderReportPosition { encoder: arg(1)? })
+                        }
+                        ENCODER_REPORT_POSITIONS => Ok(Command::EncoderReportPositions),
+                        ENCODER_RESET_POSITION => {
+                            Ok(Command::EncoderResetPosition { encoder: arg(1)? })
+                        }
+                        ENCODER_REPORT_AUTO => Ok(Command::EncoderReportAuto {
+                            enable: arg(1)? != 0,
+                        }),
+                        ENCODER_DETACH => Ok(Command::EncoderDetach { encoder: arg(1)? }),
+                        _ => Err(Error::UnknownSysEx { code: ENCODER_DATA }),
+                    },
+                    ONEWIRE_DATA => {
+                        let pin = arg(1)?;
+                        match arg(0)? {
+                            ONEWIRE_CONFIG_REQUEST => Ok(Command::OneWireConfig {
+                                pin,
+                                power: arg(2)? != 0,
+                            }),
+                            ONEWIRE_SEARCH_REQUEST => Ok(Command::OneWireSearch { pin }),
+                            ONEWIRE_SEARCH_ALARMS_REQUEST => {
+                                Ok(Command::OneWireSearchAlarms { pin })
+                            }
+                            bits @ 0x00..=0x3F => {
+                                let data = decode_packed(data.get(2..).unwrap_or_default());
+                                let bytes = |range: std::ops::Range<usize>| {
+                                    data.get(range).with_context(|| MessageTooShortSnafu)
+                                };
+                                let mut request = OneWireRequest {
+                                    reset: bits & ONEWIRE_RESET_REQUEST_BIT != 0,
+                                    skip: bits & ONEWIRE_SKIP_REQUEST_BIT != 0,
+                                    ..Default::default()
+                                };
+                                if bits & ONEWIRE_SELECT_REQUEST_BIT != 0 {
+                                    let address = bytes(0..8)?.try_into().expect("8 bytes");
+                                    request.select = Some(OneWireAddress::new(address)?);
+                                }
+                                if bits & ONEWIRE_READ_REQUEST_BIT != 0 {
+                                    let read = bytes(8..12)?;
+                                    request.read = Some((
+                                        u16::from_le_bytes([read[0], read[1]]),
+                                        u16::from_le_bytes([read[2], read[3]]),
+                                    ));
+                                }
+                                if bits & ONEWIRE_DELAY_REQUEST_BIT != 0 {
+                                    let delay = bytes(12..16)?.try_into().expect("4 bytes");
+                                    request.delay = Some(u32::from_le_bytes(delay));
+                                }
+                                if bits & ONEWIRE_WRITE_REQUEST_BIT != 0 {
+                                    let write = bytes(ONEWIRE_REQUEST_HEADER_BYTES..data.len())?;
+                                    request.write = Some(write.to_vec());
+                                }
+                                Ok(Command::OneWireRequest { pin, request })
+                            }
+                            _ => Err(Error::UnknownSysEx { code: ONEWIRE_DATA }),
+                        }
+                    }
+                    SCHEDULER_DATA => {
+                        let delay = |data: &[u8]| -> Result<u32> {
+                            let bytes = decode_packed(data);
+                            let bytes = bytes.get(..4).with_context(|| MessageTooShortSnafu)?;
+                            Ok(u32::from_le_bytes(bytes.try_into().expect("4 bytes")))
+                        };
+                        match arg(0)? {
+                            SCHEDULER_CREATE_TASK => Ok(Command::SchedulerCreateTask {
+                                task: arg(1)?,
+                                length: decode_u14(arg(2)?, arg(3)?),
+                            }),
+                            SCHEDULER_DELETE_TASK => {
+                                Ok(Command::SchedulerDeleteTask { task: arg(1)? })
+                            }
+                            SCHEDULER_ADD_TO_TASK => Ok(Command::SchedulerAddToTask {
+                                task: arg(1)?,
+                                data: decode_packed(&data[2..]),
+                            }),
+                            SCHEDULER_DELAY_TASK => Ok(Command::SchedulerDelayTask {
+                                delay: delay(&data[1..])?,
+                            }),
+                            SCHEDULER_SCHEDULE_TASK => Ok(Command::SchedulerScheduleTask {
+                                task: arg(1)?,
+                                delay: delay(&data[2..])?,
+                            }),
+                            SCHEDULER_QUERY_ALL_TASKS => Ok(Command::SchedulerQueryAllTasks),
+                            SCHEDULER_QUERY_TASK => {
+                                Ok(Command::SchedulerQueryTask { task: arg(1)? })
+                            }
+                            SCHEDULER_RESET => Ok(Command::SchedulerReset),
+                            _ => Err(Error::UnknownSysEx {
+                                code: SCHEDULER_DATA,
+                            }),
+                        }
+                    }
+                    SPI_DATA => match arg(0)? {
+                        SPI_BEGIN => Ok(Command::SpiBegin { channel: arg(1)? }),
+                        SPI_DEVICE_CONFIG => Ok(Command::SpiDeviceConfig {
+                            device: arg(1)?.into(),
+                            config: SpiDeviceConfig {
+                                mode: (arg(2)? >> 1).into(),
+                                bit_order: (arg(2)? & 0x01).try_into()?,
+                                max_speed: decode_u32(
+                                    data.get(3..8).with_context(|| MessageTooShortSnafu)?,
+                                ),
+                                cs_pin: match arg(9)? & 0x01 {
+                                    0 => None,
+                                    _ => Some(arg(10)?),
+                                },
+                                cs_active_high: arg(9)? & 0x02 != 0,
+                            },
+                        }),
+                        SPI_TRANSFER => Ok(Command::SpiTransfer {
+                            device: arg(1)?.into(),
+                            request_id: arg(2)?,
+                            deselect: arg(3)? != 0,
+                            data: decode_bytes(
+                                data.get(5..).with_context(|| MessageTooShortSnafu)?,
+                            ),
+                        }),
+                        SPI_WRITE => Ok(Command::SpiWrite {
+                            device: arg(1)?.into(),
+                            request_id: arg(2)?,
+                            deselect: arg(3)? != 0,
+                            data: decode_bytes(
+                                data.get(5..).with_context(|| MessageTooShortSnafu)?,
+                            ),
+                        }),
+                        SPI_READ => Ok(Command::SpiRead {
+                            device: arg(1)?.into(),
+                            request_id: arg(2)?,
+                            deselect: arg(3)? != 0,
+                            size: arg(4)?,
+                        }),
+                        SPI_END => Ok(Command::SpiEnd { channel: arg(1)? }),
+                        _ => Err(Error::UnknownSysEx { code: SPI_DATA }),
+                    },
+                    SHIFT_DATA => match arg(0)? {
+                        SHIFT_OUT => Ok(Command::ShiftOut {
+                            data_pin: arg(1)?,
+                            clock_pin: arg(2)?,
+                            bit_order: arg(3)?.try_into()?,
+                            data: decode_bytes(&data[4..]),
+                        }),
+                        SHIFT_IN => Ok(Command::ShiftIn {
+                            data_pin: arg(1)?,
+                            clock_pin: arg(2)?,
+                            bit_order: arg(3)?.try_into()?,
+                            size: arg(4)?,
+                        }),
+                        _ => Err(Error::UnknownSysEx { code: SHIFT_DATA }),
+                    },
+                    SERIAL_DATA => {
+                        let port = (arg(0)? & 0x0F).try_into()?;
+                        match arg(0)? & 0xF0 {
+                            SERIAL_CONFIG => Ok(Command::SerialConfig {
+                                port,
+                                baud: arg(1)? as u32
+                                    | (arg(2)? as u32) << 7
+                                    | (arg(3)? as u32) << 14,
+                                pins: match data.len() {
+                                    6.. => Some((data[4], data[5])),
+                                    _ => None,
+                                },
+                            }),
+                            SERIAL_WRITE => Ok(Command::SerialWrite {
+                                port,
+                                data: decode_bytes(&data[1..]),
+                            }),
+                            SERIAL_READ if arg(1)? == SERIAL_STOP_READING => {
+                                Ok(Command::SerialStopReading { port })
+                            }
+                            SERIAL_READ => Ok(Command::SerialRead {
+                                port,
+                                max_bytes: match data.len() {
+                                    4.. => Some(decode_u14(data[2], data[3])),
+                                    _ => None,
+                                },
+                            }),
+                            SERIAL_CLOSE => Ok(Command::SerialClose { port }),
+                            SERIAL_FLUSH => Ok(Command::SerialFlush { port }),
+                            SERIAL_LISTEN => Ok(Command::SerialListen { port }),
+                            _ => Err(Error::UnknownSysEx { code: SERIAL_DATA }),
+                        }
+                    }
+                    STEPPER_DATA => match arg(0)? {
+                        STEPPER_CONFIG => Ok(Command::StepperConfig {
+                            device: arg(1)?,
+                            steps_per_revolution: decode_u14(arg(3)?, arg(4)?),
+                            interface: match arg(2)? & 0x07 {
+                                STEPPER_DRIVER => StepperInterface::Driver {
+                                    direction_pin: arg(5)?,
+                                    step_pin: arg(6)?,
+                                },
+                                STEPPER_TWO_WIRE => StepperInterface::TwoWire {
+                                    pins: [arg(5)?, arg(6)?],
+                                },
+                                _ => StepperInterface::FourWire {
+                                    pins: [arg(5)?, arg(6)?, arg(7)?, arg(8)?],
+                                },
+                            },
+                        }),
+                        STEPPER_STEP => Ok(Command::StepperStep {
+                            device: arg(1)?,
+                            direction: match arg(2)? {
+                                STEPPER_CW => StepperDirection::Clockwise,
+                                _ => StepperDirection::CounterClockwise,
+                            },
+                            steps: arg(3)? as u32 | (arg(4)? as u32) << 7 | (arg(5)? as u32) << 14,
+                            speed: decode_u14(arg(6)?, arg(7)?),
+                            ramp: match data.len() {
+                                12.. => Some(StepperRamp {
+                                    acceleration: decode_u14(data[8], data[9]),
+                                    deceleration: decode_u14(data[10], data[11]),
+                                }),
+                                _ => None,
+                            },
+                        }),
+                        _ => Err(Error::UnknownSysEx { code: STEPPER_DATA }),
+                    },
+                    STRING_DATA => Ok(Command::StringData(
+                        String::from_utf8_lossy(&decode_bytes(data)).into_owned(),
+                    )),
+                    code => Err(Error::UnknownSysEx { code }),
+                }
+            }
+            byte => Err(Error::BadByte { byte }),
+        }
+    }
+}
+
+/// Check that `value` of the command field `name` is at most `max`.
+fn field<V: Copy + Into<u32>>(name: &'static str, value: V, max: u32) -> Result<V> {
+    ensure!(
+        value.into() <= max,
+        InvalidCommandFieldSnafu {
+            field: name,
+            value: value.into(),
+            max
+        }
+    );
+    Ok(value)
+}
+
+/// Check that the index of the serial `port` fits in its two bits.
+fn serial_port(port: SerialPortId) -> Result<u8> {
+    let (SerialPortId::Hardware(index) | SerialPortId::Software(index)) = port;
+    field("port", index, 0x03)?;
+    Ok(port.into())
+}
+
+/// Check that the channel and ID of the SPI `device` fit in their three and four bits.
+fn spi_device(device: SpiDevice) -> Result<u8> {
+    field("channel", device.channel, 0x07)?;
+    field("id", device.id, 0x0F)?;
+    Ok(device.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn command_frames() {
+        let frames = [
+            (
+                Command::AnalogWrite { pin: 3, value: 200 },
+                vec![0xE3, 0x48, 0x01],
+            ),
+            (
+                Command::ExtendedAnalog {
+                    pin: 20,
+                    value: 0x1234,
+                },
+                vec![0xF0, 0x6F, 0x14, 0x34, 0x24, 0xF7],
+            ),
+            (
+                Command::DigitalWrite {
+                    port: 1,
+                    mask: 0xA5,
+                },
+                vec![0x91, 0x25, 0x01],
+            ),
+            (
+                Command::ReportAnalog {
+                    channel: 2,
+                    enable: true,
+                },
+                vec![0xC2, 0x01],
+            ),
+            (
+                Command::SetPinMode {
+                    pin: 13,
+                    mode: PinMode::Output,
+                },
+                vec![0xF4, 0x0D, 0x01],
+            ),
+            (
+                Command::SetDigitalPinValue {
+                    pin: 13,
+                    value: true,
+                },
+                vec![0xF5, 0x0D, 0x01],
+            ),
+            (Command::ReportVersion, vec![0xF9]),
+            (Command::SystemReset, vec![0xFF]),
+            (Command::CapabilityQuery, vec![0xF0, 0x6B, 0xF7]),
+            (
+                Command::PinStateQuery { pin: 5 },
+                vec![0xF0, 0x6D, 0x05, 0xF7],
+            ),
+            (
+                Command::SamplingInterval { interval: 1000 },
+                vec![0xF0, 0x7A, 0x68, 0x07, 0xF7],
+            ),
+            (
+                Command::I2CConfig { delay: 1000 },
+                vec![0xF0, 0x78, 0x68, 0x07, 0xF7],
+            ),
+            (
+                Command::I2CRequest(I2CRequest::new(
+                    0x09,
+                    I2COperation::Read {
+                        register: Some(0x67),
+                        size: 3,
+                    },
+                )),
+                vec![0xF0, 0x76, 0x09, 0x08, 0x67, 0x00, 0x03, 0x00, 0xF7],
+            ),
+            (
+                Command::I2CRequest(
+                    I2CRequest::new(
+                        0x2A5,
+                        I2COperation::ReadContinuously {
+                            register: None,
+                            size: 2,
+                        },
+                    )
+                    .ten_bit(true)
+                    .auto_restart(true),
+                ),
+                vec![0xF0, 0x76, 0x25, 0x75, 0x02, 0x00, 0xF7],
+            ),
+            (
+                Command::I2CRequest(I2CRequest::new(
+                    0x09,
+                    I2COperation::Write { data: vec![0xFF] },
+                )),
+                vec![0xF0, 0x76, 0x09, 0x00, 0x7F, 0x01, 0xF7],
+            ),
+            (
+                Command::I2CRequest(I2CRequest::new(0x09, I2COperation::StopReading)),
+                vec![0xF0, 0x76, 0x09, 0x18, 0xF7],
+            ),
+            (
+                Command::ServoConfig {
+                    pin: 9,
+                    min_pulse: 544,
+                    max_pulse: 2400,
+                },
+                vec![0xF0, 0x70, 0x09, 0x20, 0x04, 0x60, 0x12, 0xF7],
+            ),
+            (
+                Command::EncoderAttach {
+                    encoder: 0,
+                    pin_a: 2,
+                    pin_b: 3,
+                },
+                vec![0xF0, 0x61, 0x00, 0x00, 0x02, 0x03, 0xF7],
+            ),
+            (
+                Command::EncoderReportAuto { enable: true },
+                vec![0xF0, 0x61, 0x04, 0x01, 0xF7],
+            ),
+            (
+                Command::StepperConfig {
+                    device: 0,
+                    steps_per_revolution: 200,
+                    interface: StepperInterface::Driver {
+                        direction_pin: 2,
+                        step_pin: 3,
+                    },
+                },
+                vec![0xF0, 0x72, 0x00, 0x00, 0x01, 0x48, 0x01, 0x02, 0x03, 0xF7],
+            ),
+            (
+                Command::StepperStep {
+                    device: 0,
+                    direction: StepperDirection::CounterClockwise,
+                    steps: 200,
+                    speed: 1000,
+                    ramp: None,
+                },
+                vec![
+                    0xF0, 0x72, 0x01, 0x00, 0x01, 0x48, 0x01, 0x00, 0x68, 0x07, 0xF7,
+                ],
+            ),
+            (
+                Command::AccelStepperConfig {
+                    device: 0,
+                    config: AccelStepperConfig::new(AccelStepperInterface::Driver {
+                        step_pin: 2,
+                        direction_pin: 3,
+                    })
+                    .step_size(StepSize::Half)
+                    .enable_pin(4)
+                    .invert(0x01),
+                },
+                vec![0xF0, 0x62, 0x00, 0x00, 0x13, 0x02, 0x03, 0x04, 0x01, 0xF7],
+            ),
+            (
+                Command::AccelStepperTo {
+                    device: 0,
+                    position: -1000,
+                },
+                vec![0xF0, 0x62, 0x03, 0x00, 0x68, 0x07, 0x00, 0x00, 0x08, 0xF7],
+            ),
+            (
+                Command::AccelStepperSetSpeed {
+                    device: 0,
+                    speed: 100.0,
+                },
+                vec![0xF0, 0x62, 0x09, 0x00, 0x64, 0x00, 0x00, 0x2C, 0xF7],
+            ),
+            (
+                Command::MultiStepperConfig {
+                    group: 0,
+                    devices: vec![0, 1],
+                },
+                vec![0xF0, 0x62, 0x20, 0x00, 0x00, 0x01, 0xF7],
+            ),
+            (
+                Command::SchedulerCreateTask {
+                    task: 0,
+                    length: 12,
+                },
+                vec![0xF0, 0x7B, 0x00, 0x00, 0x0C, 0x00, 0xF7],
+            ),
+            (
+                Command::SchedulerDelayTask { delay: 400 },
+                vec![0xF0, 0x7B, 0x03, 0x10, 0x03, 0x00, 0x00, 0x00, 0xF7],
+            ),
+            (
+                Command::SchedulerQueryTask { task: 0 },
+                vec![0xF0, 0x7B, 0x06, 0x00, 0xF7],
+            ),
+            (
+                Command::ShiftOut {
+                    data_pin: 4,
+                    clock_pin: 5,
+                    bit_order: BitOrder::LsbFirst,
+                    data: vec![0xA5],
+                },
+                vec![0xF0, 0x75, 0x01, 0x04, 0x05, 0x00, 0x25, 0x01, 0xF7],
+            ),
+            (
+                Command::ShiftIn {
+                    data_pin: 4,
+                    clock_pin: 5,
+                    bit_order: BitOrder::MsbFirst,
+                    size: 2,
+                },
+                vec![0xF0, 0x75, 0x02, 0x04, 0x05, 0x01, 0x02, 0xF7],
+            ),
+            (
+                Command::SpiBegin { channel: 0 },
+                vec![0xF0, 0x68, 0x00, 0x00, 0xF7],
+            ),
+            (
+                Command::SpiDeviceConfig {
+                    device: SpiDevice::new(0, 1),
+                    config: SpiDeviceConfig::new(1_000_000)
+                        .mode(SpiMode::Mode3)
+                        .cs_pin(10),
+                },
+                vec![
+                    0xF0, 0x68, 0x01, 0x08, 0x07, 0x40, 0x04, 0x3D, 0x00, 0x00, 0x00, 0x01, 0x0A,
+                    0xF7,
+                ],
+            ),
+            (
+                Command::SpiTransfer {
+                    device: SpiDevice::new(0, 1),
+                    request_id: 3,
+                    deselect: true,
+                    data: vec![0xA5],
+                },
+                vec![0xF0, 0x68, 0x02, 0x08, 0x03, 0x01, 0x01, 0x25, 0x01, 0xF7],
+            ),
+            (
+                Command::SpiRead {
+                    device: SpiDevice::new(0, 1),
+                    request_id: 4,
+                    deselect: false,
+                    size: 2,
+                },
+                vec![0xF0, 0x68, 0x04, 0x08, 0x04, 0x00, 0x02, 0xF7],
+            ),
+            (
+                Command::OneWireConfig {
+                    pin: 2,
+                    power: true,
+                },
+                vec![0xF0, 0x73, 0x41, 0x02, 0x01, 0xF7],
+            ),
+            (
+                Command::OneWireSearch { pin: 2 },
+                vec![0xF0, 0x73, 0x40, 0x02, 0xF7],
+            ),
+            // Convert T on every DS18B20 on the bus: the write data starts after the header.
+            (
+                Command::OneWireRequest {
+                    pin: 2,
+                    request: OneWireRequest::new().reset().skip().write(&[0x44]),
+                },
+                [&[0xF0, 0x73, 0x23, 0x02][..], &[0; 18], &[0x10, 0x02, 0xF7]].concat(),
+            ),
+            // Read the scratchpad: size 9 at offset 8 and correlation ID 1 at offset 10.
+            (
+                Command::OneWireRequest {
+                    pin: 2,
+                    request: OneWireRequest::new().skip().read(9, 1),
+                },
+                [
+                    &[0xF0, 0x73, 0x0A, 0x02][..],
+                    &[0; 9],
+                    &[0x12, 0x00, 0x08],
+                    &[0; 7],
+                    &[0xF7],
+                ]
+                .concat(),
+            ),
+            (
+                Command::SerialConfig {
+                    port: SerialPortId::Software(0),
+                    baud: 9600,
+                    pins: Some((10, 11)),
+                },
+                vec![0xF0, 0x60, 0x18, 0x00, 0x4B, 0x00, 0x0A, 0x0B, 0xF7],
+            ),
+            (
+                Command::SerialWrite {
+                    port: SerialPortId::Hardware(1),
+                    data: b"hi".to_vec(),
+                },
+                vec![0xF0, 0x60, 0x21, 0x68, 0x00, 0x69, 0x00, 0xF7],
+            ),
+            (
+                Command::SerialRead {
+                    port: SerialPortId::Hardware(1),
+                    max_bytes: Some(200),
+                },
+                vec![0xF0, 0x60, 0x31, 0x00, 0x48, 0x01, 0xF7],
+            ),
+            (
+                Command::SerialStopReading {
+                    port: SerialPortId::Hardware(1),
+                },
+                vec![0xF0, 0x60, 0x31, 0x01, 0xF7],
+            ),
+            (
+                Command::StringData("hi".to_string()),
+                vec![0xF0, 0x71, 0x68, 0x00, 0x69, 0x00, 0xF7],
+            ),
+        ];
+        for (command, frame) in frames {
+            assert_eq!(command.encode().unwrap(), frame, "{command:?}");
+            assert_eq!(Command::decode(&frame).unwrap(), command, "{frame:02X?}");
+        }
+    }
+
+    #[test]
+    fn onewire_request_round_trip() {
+        let address = OneWireAddress::new([0x02, 0x1C, 0xB8, 0x01, 0x00, 0x00, 0x00, 0xA2]);
+        let request = OneWireRequest::new()
+            .reset()
+            .select(address.unwrap())
+            .write(&[0xBE])
+            .read(9, 0x1234)
+            .delay(750);
+        let command = Command::OneWireRequest { pin: 2, request };
+        assert_eq!(
+            Command::decode(&command.encode().unwrap()).unwrap(),
+            command
+        );
+    }
+
+    #[test]
+    fn short_spi_frames() {
+        for frame in [
+            [0xF0, 0x68, 0x02, 0x00, 0x01, 0x00, 0xF7],
+            [0xF0, 0x68, 0x03, 0x00, 0x01, 0x00, 0xF7],
+        ] {
+            assert!(matches!(
+                Command::decode(&frame),
+                Err(Error::MessageTooShort)
+            ));
+        }
+    }
+
+    #[test]
+    fn reject_fields_out_of_range() {
+        let commands = [
+            Command::AnalogWrite { pin: 16, value: 0 },
+            Command::AnalogWrite {
+                pin: 3,
+                value: 0x4000,
+            },
+            Command::ExtendedAnalog { pin: 128, value: 0 },
+            Command::DigitalWrite { port: 16, mask: 0 },
+            Command::ReportAnalog {
+                channel: 16,
+                enable: true,
+            },
+            Command::ReportDigital {
+                port: 16,
+                enable: true,
+            },
+            Command::SetPinMode {
+                pin: 128,
+                mode: PinMode::Output,
+            },
+            Command::SetPinMode {
+                pin: 13,
+                mode: PinMode::Unknown(0x80),
+            },
+            Command::SetDigitalPinValue {
+                pin: 128,
+                value: true,
+            },
+            Command::PinStateQuery { pin: 128 },
+            Command::SamplingInterval { interval: 0x4000 },
+            Command::I2CRequest(I2CRequest::new(0x80, I2COperation::StopReading)),
+            Command::I2CRequest(I2CRequest::new(0x400, I2COperation::StopReading).ten_bit(true)),
+            Command::I2CRequest(I2CRequest::new(
+                0x09,
+                I2COperation::Read {
+                    register: Some(0x4000),
+                    size: 1,
+                },
+            )),
+            Command::ServoConfig {
+                pin: 9,
+                min_pulse: 544,
+                max_pulse: 0x4000,
+            },
+            Command::EncoderAttach {
+                encoder: 0x40,
+                pin_a: 2,
+                pin_b: 3,
+            },
+            Command::StepperStep {
+                device: 0,
+                direction: StepperDirection::Clockwise,
+                steps: 0x20_0000,
+                speed: 1000,
+                ramp: None,
+            },
+            Command::StepperStep {
+                device: 0,
+                direction: StepperDirection::Clockwise,
+                steps: 200,
+                speed: 1000,
+                ramp: Some(StepperRamp {
+                    acceleration: 0x4000,
+                    deceleration: 0,
+                }),
+            },
+            Command::AccelStepperConfig {
+                device: 0,
+                config: AccelStepperConfig::new(AccelStepperInterface::Driver {
+                    step_pin: 2,
+                    direction_pin: 3,
+                })
+                .invert(0x20),
+            },
+            Command::MultiStepperConfig {
+                group: 0,
+                devices: vec![0, 128],
+            },
+            Command::SchedulerCreateTask {
+                task: 0,
+                length: 0x4000,
+            },
+            Command::SchedulerDeleteTask { task: 128 },
+            Command::ShiftIn {
+                data_pin: 4,
+                clock_pin: 5,
+                bit_order: BitOrder::MsbFirst,
+                size: 128,
+            },
+            Command::SpiBegin { channel: 8 },
+            Command::SpiRead {
+                device: SpiDevice::new(0, 16),
+                request_id: 0,
+                deselect: true,
+                size: 1,
+            },
+            Command::SpiRead {
+                device: SpiDevice::new(0, 1),
+                request_id: 128,
+                deselect: true,
+                size: 1,
+            },
+            Command::SpiWrite {
+                device: SpiDevice::new(0, 1),
+                request_id: 0,
+                deselect: true,
+                data: vec![0; 128],
+            },
+            Command::OneWireSearch { pin: 128 },
+            Command::SerialConfig {
+                port: SerialPortId::Hardware(1),
+                baud: 0x20_0000,
+                pins: None,
+            },
+            Command::SerialConfig {
+                port: SerialPortId::Software(0),
+                baud: 9600,
+                pins: Some((10, 128)),
+            },
+            Command::SerialListen {
+                port: SerialPortId::Software(4),
+            },
+        ];
+        for command in commands {
+            assert!(
+                matches!(command.encode(), Err(Error::InvalidCommandField { .. })),
+                "{command:?}"
+            );
+        }
+    }
+}
@@ -45,6 +45,10 @@ pub const END_SYSEX: u8 = 0xF7;
 pub const SERIAL_DATA: u8 = 0x60;
 /// Reply with encoders current positions
 pub const ENCODER_DATA: u8 = 0x61;
+/// Control a stepper motor with acceleration, alone or in a group
+pub const ACCELSTEPPER_DATA: u8 = 0x62;
+/// Configure and talk to SPI devices
+pub const SPI_DATA: u8 = 0x68;
 /// Set max angle, minPulse, maxPulse, freq
 pub const SERVO_CONFIG: u8 = 0x70;
 /// String message with 14-bits per char
@@ -116,14 +120,189 @@ pub const PIN_MODE_IGNORE: u8 = 0x7F;
 pub const TOTAL_PIN_MODES: u8 = 13;
 
 // I2C additions.
+/// Write to the I2C device
 pub const I2C_WRITE: u8 = 0x00;
+/// Read once from the I2C device, before shifting into the read/write mode bits
 pub const I2C_READ: u8 = 0x01;
+/// Read once from the I2C device, as the read/write mode bits of an I2C request
+pub const I2C_MODE_READ: u8 = I2C_READ << 3;
+/// Read from the I2C device at every sampling interval
 pub const I2C_READ_CONTINUOUSLY: u8 = 0x10;
+/// Stop reading continuously from the I2C device
 pub const I2C_STOP_READING: u8 = 0x18;
+/// Bits of the read/write mode in an I2C request
 pub const I2C_READ_WRITE_MODE_MASK: u8 = 0x18;
+/// Address is a 10-bit address
 pub const I2C_10BIT_ADDRESS_MODE_MASK: u8 = 0x20;
+/// Send a repeated start instead of a stop condition after the transmission
 pub const I2C_END_TX_MASK: u8 = 0x40;
 
+// Encoder additions.
+/// Attach an encoder to a pair of pins
+pub const ENCODER_ATTACH: u8 = 0x00;
+/// Ask for the position of an encoder
+pub const ENCODER_REPORT_POSITION: u8 = 0x01;
+/// Ask for the positions of all encoders
+pub const ENCODER_REPORT_POSITIONS: u8 = 0x02;
+/// Reset the position of an encoder to zero
+pub const ENCODER_RESET_POSITION: u8 = 0x03;
+/// Enable or disable reporting the positions at every sampling interval
+pub const ENCODER_REPORT_AUTO: u8 = 0x04;
+/// Detach an encoder
+pub const ENCODER_DETACH: u8 = 0x05;
+/// Position is negative
+pub const ENCODER_DIRECTION_MASK: u8 = 0x40;
+/// Encoder number of a position
+pub const ENCODER_CHANNEL_MASK: u8 = 0x3F;
+
+// Stepper additions.
+/// Configure a stepper
+pub const STEPPER_CONFIG: u8 = 0x00;
+/// Move a stepper
+pub const STEPPER_STEP: u8 = 0x01;
+/// Step and direction driver interface
+pub const STEPPER_DRIVER: u8 = 0x01;
+/// Two wire interface
+pub const STEPPER_TWO_WIRE: u8 = 0x02;
+/// Four wire interface
+pub const STEPPER_FOUR_WIRE: u8 = 0x04;
+/// Move clockwise
+pub const STEPPER_CW: u8 = 0x00;
+/// Move counterclockwise
+pub const STEPPER_CCW: u8 = 0x01;
+
+// AccelStepper additions.
+/// Configure a stepper
+pub const ACCELSTEPPER_CONFIG: u8 = 0x00;
+/// Set the current position of a stepper to zero
+pub const ACCELSTEPPER_ZERO: u8 = 0x01;
+/// Move a stepper a number of steps
+pub const ACCELSTEPPER_STEP: u8 = 0x02;
+/// Move a stepper to an absolute position
+pub const ACCELSTEPPER_TO: u8 = 0x03;
+/// Enable or disable the driver of a stepper
+pub const ACCELSTEPPER_ENABLE: u8 = 0x04;
+/// Stop a stepper
+pub const ACCELSTEPPER_STOP: u8 = 0x05;
+/// Ask for or reply with the position of a stepper
+pub const ACCELSTEPPER_REPORT_POSITION: u8 = 0x06;
+/// Set the acceleration of a stepper
+pub const ACCELSTEPPER_SET_ACCELERATION: u8 = 0x08;
+/// Set the speed of a stepper
+pub const ACCELSTEPPER_SET_SPEED: u8 = 0x09;
+/// A stepper completed its move
+pub const ACCELSTEPPER_MOVE_COMPLETE: u8 = 0x0A;
+/// Configure a group of steppers
+pub const MULTISTEPPER_CONFIG: u8 = 0x20;
+/// Move a group of steppers to absolute positions
+pub const MULTISTEPPER_TO: u8 = 0x21;
+/// Stop a group of steppers
+pub const MULTISTEPPER_STOP: u8 = 0x23;
+/// A group of steppers completed its move
+pub const MULTISTEPPER_MOVE_COMPLETE: u8 = 0x24;
+
+// OneWire additions.
+/// Search for devices on the bus
+pub const ONEWIRE_SEARCH_REQUEST: u8 = 0x40;
+/// Configure the bus pin and parasitic power
+pub const ONEWIRE_CONFIG_REQUEST: u8 = 0x41;
+/// Addresses of the devices found on the bus
+pub const ONEWIRE_SEARCH_REPLY: u8 = 0x42;
+/// Bytes read from the bus
+pub const ONEWIRE_READ_REPLY: u8 = 0x43;
+/// Search for devices in an alarm state
+pub const ONEWIRE_SEARCH_ALARMS_REQUEST: u8 = 0x44;
+/// Addresses of the devices in an alarm state
+pub const ONEWIRE_SEARCH_ALARMS_REPLY: u8 = 0x45;
+/// Reset the bus
+pub const ONEWIRE_RESET_REQUEST_BIT: u8 = 0x01;
+/// Address all devices on the bus
+pub const ONEWIRE_SKIP_REQUEST_BIT: u8 = 0x02;
+/// Address a single device
+pub const ONEWIRE_SELECT_REQUEST_BIT: u8 = 0x04;
+/// Read from the bus
+pub const ONEWIRE_READ_REQUEST_BIT: u8 = 0x08;
+/// Delay after the request
+pub const ONEWIRE_DELAY_REQUEST_BIT: u8 = 0x10;
+/// Write to the bus
+pub const ONEWIRE_WRITE_REQUEST_BIT: u8 = 0x20;
+
+// Scheduler additions.
+/// Create a task
+pub const SCHEDULER_CREATE_TASK: u8 = 0x00;
+/// Delete a task
+pub const SCHEDULER_DELETE_TASK: u8 = 0x01;
+/// Append to the body of a task
+pub const SCHEDULER_ADD_TO_TASK: u8 = 0x02;
+/// Delay the running task, only used in task bodies
+pub const SCHEDULER_DELAY_TASK: u8 = 0x03;
+/// Schedule a task to run
+pub const SCHEDULER_SCHEDULE_TASK: u8 = 0x04;
+/// Ask for the IDs of all tasks
+pub const SCHEDULER_QUERY_ALL_TASKS: u8 = 0x05;
+/// Ask for the state of a task
+pub const SCHEDULER_QUERY_TASK: u8 = 0x06;
+/// Delete all tasks
+pub const SCHEDULER_RESET: u8 = 0x07;
+/// A task failed
+pub const SCHEDULER_ERROR_TASK_REPLY: u8 = 0x08;
+/// IDs of all tasks
+pub const SCHEDULER_QUERY_ALL_TASKS_REPLY: u8 = 0x09;
+/// State of a task
+pub const SCHEDULER_QUERY_TASK_REPLY: u8 = 0x0A;
+
+// Shift additions.
+/// Shift bytes out of a data pin
+pub const SHIFT_OUT: u8 = 0x01;
+/// Shift bytes into a data pin
+pub const SHIFT_IN: u8 = 0x02;
+/// Bytes shifted into a data pin
+pub const SHIFT_IN_REPLY: u8 = 0x03;
+/// Shift the least significant bit first
+pub const LSB_FIRST: u8 = 0x00;
+/// Shift the most significant bit first
+pub const MSB_FIRST: u8 = 0x01;
+
+// SPI additions.
+/// Initialize an SPI channel
+pub const SPI_BEGIN: u8 = 0x00;
+/// Configure an SPI device
+pub const SPI_DEVICE_CONFIG: u8 = 0x01;
+/// Write words to an SPI device and read the words it sends back
+pub const SPI_TRANSFER: u8 = 0x02;
+/// Write words to an SPI device
+pub const SPI_WRITE: u8 = 0x03;
+/// Read words from an SPI device
+pub const SPI_READ: u8 = 0x04;
+/// Words read from an SPI device
+pub const SPI_REPLY: u8 = 0x05;
+/// Release an SPI channel
+pub const SPI_END: u8 = 0x06;
+
+// Serial additions.
+/// Configure a serial port
+pub const SERIAL_CONFIG: u8 = 0x10;
+/// Write to a serial port
+pub const SERIAL_WRITE: u8 = 0x20;
+/// Start or stop reading from a serial port
+pub const SERIAL_READ: u8 = 0x30;
+/// Data read from a serial port
+pub const SERIAL_REPLY: u8 = 0x40;
+/// Close a serial port
+pub const SERIAL_CLOSE: u8 = 0x50;
+/// Flush a serial port
+pub const SERIAL_FLUSH: u8 = 0x60;
+/// Listen on a software serial port, only one can listen at a time
+pub const SERIAL_LISTEN: u8 = 0x70;
+/// Read from the serial port continuously
+pub const SERIAL_READ_CONTINUOUSLY: u8 = 0x00;
+/// Stop reading from the serial port
+pub const SERIAL_STOP_READING: u8 = 0x01;
+/// First hardware serial port
+pub const HW_SERIAL0: u8 = 0x00;
+/// First software serial port
+pub const SW_SERIAL0: u8 = 0x08;
+
 // Other values
 /// Default analog resolution value
 pub const DEFAULT_ANALOG_RESOLUTION: u8 = 10;
@@ -131,3 +310,24 @@ pub const DEFAULT_ANALOG_RESOLUTION: u8 = 10;
 pub const DEFAULT_PWM_RESOLUTION: u8 = 10;
 /// Default PWM resolution value
 pub const DEFAULT_SERVO_RESOLUTION: u8 = 14;
+/// Smallest servo value the firmware treats as a pulse width in microseconds instead of an angle
+pub const SERVO_MIN_PULSE: u16 = 544;
+/// Default servo pulse width in microseconds at 180 degrees
+pub const SERVO_MAX_PULSE: u16 = 2400;
+/// Bytes in a OneWire request before the bytes to write, with every field at a fixed offset
+pub const ONEWIRE_REQUEST_HEADER_BYTES: usize = 16;
+/// Most bytes of a serial write: the 62 bytes of the 64-byte SysEx buffer after `SERIAL_DATA` and
+/// the command byte, at two 7-bit bytes each
+pub const SERIAL_WRITE_MAX_BYTES: usize = 31;
+/// Most bytes of a task body in one scheduler message: the 61 bytes of the 64-byte SysEx buffer
+/// after `SCHEDULER_DATA`, the command and the task ID carry 7 bits each, 427 bits in all
+pub const SCHEDULER_ADD_TO_TASK_MAX_BYTES: usize = 53;
+/// Most bytes of a shift out: the 59 bytes of the 64-byte SysEx buffer after `SHIFT_DATA`, the
+/// command, both pins and the bit order, at two 7-bit bytes each
+pub const SHIFT_OUT_MAX_BYTES: usize = 29;
+/// Most words of an SPI transfer, write or read: the 58 bytes of the 64-byte SysEx buffer after
+/// `SPI_DATA`, the command, device, request ID, deselect flag and word count, at two 7-bit bytes
+/// each
+pub const SPI_MAX_WORDS: usize = 29;
+/// Default sampling interval in milliseconds
+pub const DEFAULT_SAMPLING_INTERVAL: u16 = 19;
@@ -0,0 +1,48 @@
+//! I2C read and write requests.
+
+/// What an [`I2CRequest`] asks the I2C device to do.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum I2COperation {
+    /// Write `data` to the device.
+    Write { data: Vec<u8> },
+    /// Read `size` bytes once, starting at `register` if the device needs one.
+    Read { register: Option<u16>, size: u16 },
+    /// Read `size` bytes at every sampling interval, starting at `register` if the device needs
+    /// one.
+    ReadContinuously { register: Option<u16>, size: u16 },
+    /// Stop reading continuously from the device.
+    StopReading,
+}
+
+/// A request to the I2C device at `address`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct I2CRequest {
+    /// 7-bit address, or 10-bit address if `ten_bit` is set.
+    pub address: u16,
+    /// Whether `address` is a 10-bit address.
+    pub ten_bit: bool,
+    /// Send a repeated start instead of a stop condition after the transmission.
+    pub auto_restart: bool,
+    pub operation: I2COperation,
+}
+impl I2CRequest {
+    /// Creates a new `I2CRequest` to the 7-bit `address`.
+    pub fn new(address: u16, operation: I2COperation) -> Self {
+        Self {
+            address,
+            ten_bit: false,
+            auto_restart: false,
+            operation,
+        }
+    }
+    /// Use a 10-bit address.
+    pub fn ten_bit(mut self, ten_bit: bool) -> Self {
+        self.ten_bit = ten_bit;
+        self
+    }
+    /// Send a repeated start instead of a stop condition after the transmission.
+    pub fn auto_restart(mut self, auto_restart: bool) -> Self {
+        self.auto_restart = auto_restart;
+        self
+    }
+}
@@ -53,10 +53,37 @@
 //! https://github.com/zankich/rust-firmata to whom should go out many thanks!
 
 use snafu::prelude::*;
+use std::collections::{HashMap, VecDeque};
 use std::io::{Read, Write};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+mod accel_stepper;
+mod capabilities;
+mod codec;
+mod command;
 mod constants;
+mod i2c;
+mod onewire;
+mod pin_mode;
+mod scheduler;
+mod serial;
+mod shift;
+mod spi;
+mod stepper;
+mod version;
+pub use accel_stepper::*;
+pub use capabilities::*;
+pub use codec::*;
+pub use command::*;
 pub use constants::*;
+pub use i2c::*;
+pub use onewire::*;
+pub use pin_mode::*;
+pub use scheduler::*;
+pub use serial::*;
+pub use shift::*;
+pub use spi::*;
+pub use stepper::*;
+pub use version::*;
 
 /// Firmata error type.
 #[derive(Debug, Snafu)]
@@ -73,6 +100,53 @@ pub enum Error {
     MessageTooShort,
     /// Pin out of bounds: {pin} ({len}).
     PinOutOfBounds { pin: u8, len: usize },
+    /// Command field {field} out of range: {value} (max {max}).
+    InvalidCommandField {
+        field: &'static str,
+        value: u32,
+        max: u32,
+    },
+    /// Pin {pin} is not an analog pin.
+    NotAnalog { pin: u8 },
+    /// Pin {pin} does not support mode {mode}.
+    UnsupportedPinMode { pin: u8, mode: PinMode },
+    /// Servo angle must be between 0 and 180 degrees: {degrees}.
+    InvalidServoAngle { degrees: i32 },
+    /// Servo pulse must be between 544 and 16383 microseconds: {micros}.
+    InvalidServoPulse { micros: i32 },
+    /// Servo pulse range must be increasing and within 0 to 16383 microseconds: {min_pulse} to
+    /// {max_pulse}.
+    InvalidServoRange { min_pulse: i32, max_pulse: i32 },
+    /// OneWire address {address:#018X} fails its CRC check.
+    InvalidOneWireAddress { address: u64 },
+    /// Stepper steps must be between -2097151 and 2097151: {steps}.
+    InvalidStepperSteps { steps: i32 },
+    /// Group {group} has {expected} steppers, got {len} positions.
+    InvalidMultiStepperPositions {
+        group: u8,
+        expected: usize,
+        len: usize,
+    },
+    /// Shift in size must be between 1 and 127 bytes: {size}.
+    InvalidShiftSize { size: i32 },
+    /// SPI read size must be at least 1 word: {size}.
+    InvalidSpiReadSize { size: i32 },
+    /// Timed out after {timeout:?}.
+    Timeout { timeout: Duration },
+    /// Scheduler delay must be at most 4294967295 milliseconds: {delay:?}.
+    InvalidSchedulerDelay { delay: Duration },
+    /// Scheduler task length must be between 1 and 16383 bytes: {length}.
+    InvalidSchedulerTaskLength { length: i32 },
+    /// Sampling interval must be between 1 and 16383 milliseconds: {interval:?}.
+    InvalidSamplingInterval { interval: Duration },
+    /// {feature} requires protocol version {required}, the board reports {version}.
+    UnsupportedFeature {
+        feature: Feature,
+        required: ProtocolVersion,
+        version: ProtocolVersion,
+    },
+    /// Analog level must not be negative: {level}.
+    InvalidAnalogLevel { level: i32 },
 }
 impl From<backoff::Error<Error>> for Error {
     fn from(value: backoff::Error<Error>) -> Self {
@@ -82,48 +156,120 @@ impl From<backoff::Error<Error>> for Error {
         }
     }
 }
+impl From<Error> for std::io::Error {
+    fn from(value: Error) -> Self {
+        match value {
+            Error::StdIoError { source } => source,
+            err => std::io::Error::other(err),
+        }
+    }
+}
 /// Result type with Firmata Error.
 pub type Result<T> = std::result::Result<T, Error>;
 
 /// Received Firmata message
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Message {
-    ProtocolVersion,
-    Analog,
-    Digital,
+    /// Firmata protocol version.
+    ProtocolVersion(ProtocolVersion),
+    /// Value of an analog channel.
+    Analog { channel: u8, value: i32 },
+    /// Levels of all pins in a digital port (collection of 8 pins) as a bitmask.
+    Digital { port: u8, mask: u8 },
+    /// SysEx message without any content.
     EmptyResponse,
-    AnalogMappingResponse,
-    CapabilityResponse,
-    PinStateResponse,
-    ReportFirmware,
-    I2CReply,
+    /// Analog channel per pin, `None` for pins that are not analog.
+    AnalogMappingResponse(Vec<Option<u8>>),
+    /// Supported modes and resolutions of every pin.
+    CapabilityResponse(Capabilities),
+    /// Current mode and value of a pin, without a mode if the pin does not exist.
+    PinStateResponse {
+        pin: u8,
+        mode: Option<PinMode>,
+        value: i32,
+    },
+    /// Firmware name and version.
+    ReportFirmware {
+        version: FirmwareVersion,
+        name: String,
+    },
+    /// Reply to an I2C read request.
+    I2CReply(I2CReply),
+    /// Text sent by the firmware, such as `Firmata.sendString` debug prints.
+    StringData(String),
+    /// Current `position` of AccelStepper `device`.
+    AccelStepperPosition { device: u8, position: i32 },
+    /// AccelStepper `device` completed its move at `position`.
+    AccelStepperMoveComplete { device: u8, position: i32 },
+    /// The steppers of a multi-stepper `group` completed their move.
+    MultiStepperMoveComplete { group: u8 },
+    /// Positions of one or all encoders.
+    EncoderPositions(Vec<EncoderPosition>),
+    /// Addresses of the devices found on the OneWire bus at `pin`, or only those in an alarm
+    /// state if `alarms` is set. Addresses that fail their CRC check are left out.
+    OneWireSearchReply {
+        pin: u8,
+        alarms: bool,
+        addresses: Vec<OneWireAddress>,
+    },
+    /// Bytes read from the OneWire bus at `pin` for the request with `correlation_id`.
+    OneWireReadReply {
+        pin: u8,
+        correlation_id: u16,
+        data: Vec<u8>,
+    },
+    /// IDs of all scheduler tasks.
+    SchedulerTasks(Vec<u8>),
+    /// State of scheduler task `id`, `None` if the task does not exist.
+    SchedulerTaskReply { id: u8, task: Option<SchedulerTask> },
+    /// Scheduler task `id` failed, with its state at the time of the error.
+    SchedulerTaskError { id: u8, task: Option<SchedulerTask> },
+    /// Stepper `device` completed its move.
+    StepperMoveComplete { device: u8 },
+    /// Data read from a serial port.
+    SerialReply { port: SerialPortId, data: Vec<u8> },
+    /// Bytes shifted into `data_pin`.
+    ShiftInReply { data_pin: u8, data: Vec<u8> },
+    /// Words read from SPI `device` for the request with `request_id`.
+    SpiReply {
+        device: SpiDevice,
+        request_id: u8,
+        data: Vec<u8>,
+    },
 }
 
 /// An I2C reply.
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct I2CReply {
     pub address: i32,
     pub register: i32,
     pub data: Vec<u8>,
 }
 
+/// Position of a rotary encoder.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EncoderPosition {
+    pub encoder: u8,
+    pub position: i32,
+}
+
 /// The current state and configuration of a pin.
 #[derive(Debug)]
 pub struct Pin {
     /// Currently configured mode.
-    pub mode: u8,
-    /// Current resolution.
+    pub mode: PinMode,
+    /// Resolution in bits in the current mode.
     pub resolution: u8,
     /// All pin modes.
-    pub modes: Vec<u8>,
+    pub modes: Vec<PinMode>,
     /// Pin value.
     pub value: i32,
 }
 impl Default for Pin {
     fn default() -> Self {
         Self {
-            mode: PIN_MODE_ANALOG,
-            modes: vec![PIN_MODE_ANALOG],
+            mode: PinMode::Analog,
+            modes: vec![PinMode::Analog],
             resolution: DEFAULT_ANALOG_RESOLUTION,
             value: 0,
         }
@@ -132,14 +278,54 @@ impl Default for Pin {
 
 /// Firmata board functionality.
 pub trait Firmata: std::fmt::Debug {
+    /// Configure AccelStepper `device`.
+    fn accel_stepper_config(&mut self, device: i32, config: AccelStepperConfig) -> Result<()>;
+    /// Enable or disable the driver of AccelStepper `device`.
+    fn accel_stepper_enable(&mut self, device: i32, enable: bool) -> Result<()>;
+    /// Move AccelStepper `device` a number of `steps`, backwards if negative. `i32::MIN` is
+    /// clamped to `-i32::MAX`, the furthest the firmware can go.
+    fn accel_stepper_move(&mut self, device: i32, steps: i32) -> Result<()>;
+    /// Get the last reported position of each AccelStepper.
+    fn accel_stepper_positions(&mut self) -> &HashMap<u8, i32>;
+    /// Set the `acceleration` of AccelStepper `device` in steps/s².
+    fn accel_stepper_set_acceleration(&mut self, device: i32, acceleration: f32) -> Result<()>;
+    /// Set the maximum `speed` of AccelStepper `device` in steps/s.
+    fn accel_stepper_set_speed(&mut self, device: i32, speed: f32) -> Result<()>;
+    /// Stop AccelStepper `device`, decelerating if it has an acceleration.
+    fn accel_stepper_stop(&mut self, device: i32) -> Result<()>;
+    /// Move AccelStepper `device` to an absolute `position`. `i32::MIN` is clamped to `-i32::MAX`,
+    /// the furthest the firmware can go.
+    fn accel_stepper_to(&mut self, device: i32, position: i32) -> Result<()>;
+    /// Set the current position of AccelStepper `device` to zero.
+    fn accel_stepper_zero(&mut self, device: i32) -> Result<()>;
+    /// Get the analog channel of the specified `pin`, if it is an analog pin.
+    fn analog_channel(&mut self, pin: i32) -> Option<u8>;
     /// Write `level` to the analog `pin`.
+    ///
+    /// Falls back to an extended analog message for pins above 15, levels that do not fit in 14
+    /// bits, or pins with a resolution over 14 bits.
     fn analog_write(&mut self, pin: i32, level: i32) -> Result<()>;
+    /// Get the supported modes and resolutions of every pin.
+    fn capabilities(&mut self) -> &Capabilities;
     /// Write `level` to the digital `pin`.
+    ///
+    /// Uses a single pin message if the protocol version supports it, and a message for the whole
+    /// port otherwise.
     fn digital_write(&mut self, pin: i32, level: i32) -> Result<()>;
+    /// Attach `encoder` to the pins of its A and B channels.
+    fn encoder_attach(&mut self, encoder: i32, pin_a: i32, pin_b: i32) -> Result<()>;
+    /// Detach `encoder`.
+    fn encoder_detach(&mut self, encoder: i32) -> Result<()>;
+    /// Get the last reported position of each encoder.
+    fn encoder_positions(&mut self) -> &HashMap<u8, i32>;
+    /// Reset the position of `encoder` to zero.
+    fn encoder_reset_position(&mut self, encoder: i32) -> Result<()>;
+    /// Write `level` to any analog `pin` using an extended analog message.
+    fn extended_analog(&mut self, pin: i32, level: i32) -> Result<()>;
     /// Get the firmware name.
     fn firmware_name(&mut self) -> &String;
-    /// Get the firmware version.
-    fn firmware_version(&mut self) -> &String;
+    /// Get the firmware version, if the board reported it.
+    fn firmware_version(&mut self) -> Option<FirmwareVersion>;
     /// Configure the `delay` in microseconds for I2C devices that require a delay between when the
     /// register is written to and the data in that register can be read.
     fn i2c_config(&mut self, delay: i32) -> Result<()>;
@@ -147,26 +333,204 @@ pub trait Firmata: std::fmt::Debug {
     fn i2c_data(&mut self) -> &mut Vec<I2CReply>;
     /// Read `size` bytes from I2C device at the specified `address`.
     fn i2c_read(&mut self, address: i32, size: i32) -> Result<()>;
+    /// Read `size` bytes from the I2C device at `address` at every sampling interval, starting at
+    /// `register` if the device needs one.
+    fn i2c_read_continuously(
+        &mut self,
+        address: i32,
+        register: Option<i32>,
+        size: i32,
+    ) -> Result<()>;
+    /// Read `size` bytes from `register` of the I2C device at `address` and wait up to `timeout`
+    /// for the reply.
+    fn i2c_read_register(
+        &mut self,
+        address: i32,
+        register: i32,
+        size: i32,
+        timeout: Duration,
+    ) -> Result<Vec<u8>>;
+    /// Send an I2C `request`, e.g. for register reads, 10-bit addresses or repeated starts.
+    fn i2c_request(&mut self, request: &I2CRequest) -> Result<()>;
+    /// Stop reading continuously from the I2C device at `address`.
+    fn i2c_stop_reading(&mut self, address: i32) -> Result<()>;
     /// Write `data` to the I2C device at the specified `address`.
     fn i2c_write(&mut self, address: i32, data: &[u8]) -> Result<()>;
+    /// Group AccelStepper `devices` to move them in sync.
+    fn multi_stepper_config(&mut self, group: i32, devices: &[i32]) -> Result<()>;
+    /// Stop the steppers of a `group`.
+    fn multi_stepper_stop(&mut self, group: i32) -> Result<()>;
+    /// Move the steppers of a `group` to absolute `positions`, in the order of the group, so they
+    /// all arrive at the same time.
+    fn multi_stepper_to(&mut self, group: i32, positions: &[i32]) -> Result<()>;
+    /// Configure `pin` as a OneWire bus, with parasitic `power` after writes if set.
+    fn onewire_config(&mut self, pin: i32, power: bool) -> Result<()>;
+    /// Get the bytes read from OneWire buses by correlation ID.
+    fn onewire_data(&mut self) -> &mut HashMap<u16, Vec<u8>>;
+    /// Run a `request` on the OneWire bus at `pin`.
+    fn onewire_request(&mut self, pin: i32, request: &OneWireRequest) -> Result<()>;
+    /// Search the OneWire bus at `pin` and wait up to `timeout` for the addresses of its devices.
+    fn onewire_search(&mut self, pin: i32, timeout: Duration) -> Result<Vec<OneWireAddress>>;
+    /// Search the OneWire bus at `pin` and wait up to `timeout` for the addresses of the devices
+    /// in an alarm state.
+    fn onewire_search_alarms(&mut self, pin: i32, timeout: Duration)
+        -> Result<Vec<OneWireAddress>>;
+    /// Get the pin of the specified analog `channel`, if the board has it.
+    fn pin_for_channel(&mut self, channel: u8) -> Option<i32>;
     /// Get pins that the board has access to.
     fn pins(&mut self) -> &Vec<Pin>;
-    /// Get the current Firmata protocol version.
-    fn protocol_version(&mut self) -> &String;
+    /// Get the current Firmata protocol version, if the board reported it.
+    fn protocol_version(&mut self) -> Option<ProtocolVersion>;
+    /// Query the board for the position of AccelStepper `device`.
+    fn query_accel_stepper_position(&mut self, device: i32) -> Result<()>;
     /// Query the board for available analog pins.
     fn query_analog_mapping(&mut self) -> Result<()>;
     /// Query the board for all available capabilities.
     fn query_capabilities(&mut self) -> Result<()>;
     /// Query the board for current firmware and protocol information.
     fn query_firmware(&mut self) -> Result<()>;
-    /// Read from the Firmata device, parse one Firmata message and return its type.
+    /// Query the board for the position of `encoder`.
+    fn query_encoder_position(&mut self, encoder: i32) -> Result<()>;
+    /// Query the board for the positions of all encoders.
+    fn query_encoder_positions(&mut self) -> Result<()>;
+    /// Query the board for the current mode and value of the specified `pin`.
+    fn query_pin_state(&mut self, pin: i32) -> Result<()>;
+    /// Query the board for the Firmata protocol version.
+    fn query_protocol_version(&mut self) -> Result<()>;
+    /// Query the board for the state of scheduler `task`.
+    fn query_scheduler_task(&mut self, task: i32) -> Result<()>;
+    /// Query the board for the IDs of all scheduler tasks.
+    fn query_scheduler_tasks(&mut self) -> Result<()>;
+    /// Read from the Firmata device, parse one Firmata message and return it.
     fn read_and_decode(&mut self) -> Result<Message>;
-    /// Set the analog reporting `state` of the specified `pin`.
+    /// Set the analog reporting `state` of the specified analog `pin`.
     fn report_analog(&mut self, pin: i32, state: i32) -> Result<()>;
     /// Set the digital reporting `state` of the specified `pin`.
     fn report_digital(&mut self, pin: i32, state: i32) -> Result<()>;
-    /// Set the `mode` of the specified `pin`.
-    fn set_pin_mode(&mut self, pin: i32, mode: u8) -> Result<()>;
+    /// Set the `state` of reporting the positions of all encoders at every sampling interval.
+    fn report_encoders(&mut self, state: i32) -> Result<()>;
+    /// Get the interval at which the board samples and reports analog and I2C data.
+    fn sampling_interval(&mut self) -> Duration;
+    /// Append `commands` to the body of scheduler `task`. Use [`Command::SchedulerDelayTask`] to
+    /// wait between commands.
+    fn scheduler_add_to_task(&mut self, task: i32, commands: &[Command]) -> Result<()>;
+    /// Create scheduler `task` with room for a body of `length` bytes, e.g. the total length of
+    /// the encoded commands it runs.
+    fn scheduler_create_task(&mut self, task: i32, length: i32) -> Result<()>;
+    /// Create scheduler `task` with a body of `commands`, sized to fit them.
+    fn scheduler_create_task_from(&mut self, task: i32, commands: &[Command]) -> Result<()>;
+    /// Delete scheduler `task`.
+    fn scheduler_delete_task(&mut self, task: i32) -> Result<()>;
+    /// Delete all scheduler tasks.
+    fn scheduler_reset(&mut self) -> Result<()>;
+    /// Run scheduler `task` after `delay`.
+    fn scheduler_schedule_task(&mut self, task: i32, delay: Duration) -> Result<()>;
+    /// Send a `string` to firmware that accepts text commands.
+    fn send_string(&mut self, string: &str) -> Result<()>;
+    /// Close the serial `port`.
+    fn serial_close(&mut self, port: SerialPortId) -> Result<()>;
+    /// Configure the serial `port` with a `baud` rate. Software serial ports also need their RX
+    /// and TX `pins`.
+    fn serial_config(
+        &mut self,
+        port: SerialPortId,
+        baud: i32,
+        pins: Option<(i32, i32)>,
+    ) -> Result<()>;
+    /// Get the data that has been read from each serial port and not consumed yet.
+    fn serial_data(&mut self) -> &mut HashMap<SerialPortId, VecDeque<u8>>;
+    /// Flush the serial `port`.
+    fn serial_flush(&mut self, port: SerialPortId) -> Result<()>;
+    /// Listen on the software serial `port`, only one software serial port can listen at a time.
+    fn serial_listen(&mut self, port: SerialPortId) -> Result<()>;
+    /// Read continuously from the serial `port`, at most `max_bytes` per report if set.
+    fn serial_read_continuously(
+        &mut self,
+        port: SerialPortId,
+        max_bytes: Option<i32>,
+    ) -> Result<()>;
+    /// Stop reading from the serial `port`.
+    fn serial_stop_reading(&mut self, port: SerialPortId) -> Result<()>;
+    /// Write `data` to the serial `port`.
+    fn serial_write(&mut self, port: SerialPortId, data: &[u8]) -> Result<()>;
+    /// Attach a servo to `pin`, with the pulse widths in microseconds at 0 and 180 degrees.
+    fn servo_config(&mut self, pin: i32, min_pulse: i32, max_pulse: i32) -> Result<()>;
+    /// Turn the servo on `pin` to an angle between 0 and 180 `degrees`.
+    fn servo_write_degrees(&mut self, pin: i32, degrees: i32) -> Result<()>;
+    /// Send a pulse width in `micros` to the servo on `pin`.
+    fn servo_write_micros(&mut self, pin: i32, micros: i32) -> Result<()>;
+    /// Write `level` to the digital `pin` using a single pin message.
+    fn set_digital_pin_value(&mut self, pin: i32, level: i32) -> Result<()>;
+    /// Set the `mode` of the specified `pin`, if the pin supports it.
+    fn set_pin_mode(&mut self, pin: i32, mode: PinMode) -> Result<()>;
+    /// Set the `interval` at which the board samples and reports analog and I2C data.
+    fn set_sampling_interval(&mut self, interval: Duration) -> Result<()>;
+    /// Shift `size` bytes into `data_pin`, clocked by `clock_pin`, and wait up to `timeout` for
+    /// them.
+    fn shift_in(
+        &mut self,
+        data_pin: i32,
+        clock_pin: i32,
+        bit_order: BitOrder,
+        size: i32,
+        timeout: Duration,
+    ) -> Result<Vec<u8>>;
+    /// Shift `data` out of `data_pin`, clocked by `clock_pin`, e.g. into a chain of 74HC595s.
+    fn shift_out(
+        &mut self,
+        data_pin: i32,
+        clock_pin: i32,
+        bit_order: BitOrder,
+        data: &[u8],
+    ) -> Result<()>;
+    /// Initialize SPI `channel`.
+    fn spi_begin(&mut self, channel: i32) -> Result<()>;
+    /// Configure SPI `device`, e.g. its mode, clock speed and chip select pin.
+    fn spi_device_config(&mut self, device: SpiDevice, config: SpiDeviceConfig) -> Result<()>;
+    /// Release SPI `channel`.
+    fn spi_end(&mut self, channel: i32) -> Result<()>;
+    /// Read `size` words from SPI `device` and wait up to `timeout` for them. The chip select pin is
+    /// deselected afterwards if `deselect` is set.
+    fn spi_read(
+        &mut self,
+        device: SpiDevice,
+        size: i32,
+        deselect: bool,
+        timeout: Duration,
+    ) -> Result<Vec<u8>>;
+    /// Write `data` to SPI `device` and wait up to `timeout` for the words it sends back. The chip
+    /// select pin is deselected afterwards if `deselect` is set.
+    fn spi_transfer(
+        &mut self,
+        device: SpiDevice,
+        data: &[u8],
+        deselect: bool,
+        timeout: Duration,
+    ) -> Result<Vec<u8>>;
+    /// Write `data` to SPI `device`. The chip select pin is deselected afterwards if `deselect` is
+    /// set.
+    fn spi_write(&mut self, device: SpiDevice, data: &[u8], deselect: bool) -> Result<()>;
+    /// Configure stepper `device` with the number of steps per revolution and how it is wired.
+    fn stepper_config(
+        &mut self,
+        device: i32,
+        steps_per_revolution: i32,
+        interface: StepperInterface,
+    ) -> Result<()>;
+    /// Move stepper `device` a number of `steps`, counterclockwise if negative, at `speed` in
+    /// 0.01 rad/s, with an optional acceleration and deceleration `ramp`.
+    fn stepper_step(
+        &mut self,
+        device: i32,
+        steps: i32,
+        speed: i32,
+        ramp: Option<StepperRamp>,
+    ) -> Result<()>;
+    /// Get how each configured stepper is wired, by device number.
+    fn steppers(&mut self) -> &HashMap<u8, StepperInterface>;
+    /// Reset the board and the cached pin and I2C state. Optionally `reinitialize` by querying
+    /// the board's firmware and capabilities again.
+    fn system_reset(&mut self, reinitialize: bool) -> Result<()>;
 }
 
 /// Firmata board functionality that retries and fallible methods.
@@ -178,6 +542,80 @@ pub trait RetryFirmata: Firmata {
             ..Default::default()
         }
     }
+    /// Configure AccelStepper `device`.
+    fn retry_accel_stepper_config(
+        &mut self,
+        device: i32,
+        config: AccelStepperConfig,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_config(device, config)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Enable or disable the driver of AccelStepper `device`.
+    fn retry_accel_stepper_enable(&mut self, device: i32, enable: bool) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_enable(device, enable)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Move AccelStepper `device` a number of `steps`, backwards if negative. `i32::MIN` is
+    /// clamped to `-i32::MAX`, the furthest the firmware can go.
+    fn retry_accel_stepper_move(&mut self, device: i32, steps: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_move(device, steps)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Set the `acceleration` of AccelStepper `device` in steps/s².
+    fn retry_accel_stepper_set_acceleration(
+        &mut self,
+        device: i32,
+        acceleration: f32,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_set_acceleration(device, acceleration)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Set the maximum `speed` of AccelStepper `device` in steps/s.
+    fn retry_accel_stepper_set_speed(&mut self, device: i32, speed: f32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_set_speed(device, speed)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Stop AccelStepper `device`, decelerating if it has an acceleration.
+    fn retry_accel_stepper_stop(&mut self, device: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_stop(device)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Move AccelStepper `device` to an absolute `position`. `i32::MIN` is clamped to `-i32::MAX`,
+    /// the furthest the firmware can go.
+    fn retry_accel_stepper_to(&mut self, device: i32, position: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_to(device, position)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Set the current position of AccelStepper `device` to zero.
+    fn retry_accel_stepper_zero(&mut self, device: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.accel_stepper_zero(device)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
     /// Write `level` to the analog `pin`.
     fn retry_analog_write(&mut self, pin: i32, level: i32) -> Result<()> {
         backoff::retry(self.backoff(), || {
@@ -194,6 +632,38 @@ pub trait RetryFirmata: Firmata {
         })
         .map_err(|e| e.into())
     }
+    /// Attach `encoder` to the pins of its A and B channels.
+    fn retry_encoder_attach(&mut self, encoder: i32, pin_a: i32, pin_b: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.encoder_attach(encoder, pin_a, pin_b)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Detach `encoder`.
+    fn retry_encoder_detach(&mut self, encoder: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.encoder_detach(encoder)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Reset the position of `encoder` to zero.
+    fn retry_encoder_reset_position(&mut self, encoder: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.encoder_reset_position(encoder)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Write `level` to any analog `pin` using an extended analog message.
+    fn retry_extended_analog(&mut self, pin: i32, level: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.extended_analog(pin, level)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
     /// Configure the `delay` in microseconds for I2C devices that require a delay between when the
     /// register is written to and the data in that register can be read.
     fn retry_i2c_config(&mut self, delay: i32) -> Result<()> {
@@ -210,6 +680,50 @@ pub trait RetryFirmata: Firmata {
         })
         .map_err(|e| e.into())
     }
+    /// Read `size` bytes from the I2C device at `address` at every sampling interval, starting at
+    /// `register` if the device needs one.
+    fn retry_i2c_read_continuously(
+        &mut self,
+        address: i32,
+        register: Option<i32>,
+        size: i32,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.i2c_read_continuously(address, register, size)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Read `size` bytes from `register` of the I2C device at `address` and wait up to `timeout`
+    /// for the reply.
+    fn retry_i2c_read_register(
+        &mut self,
+        address: i32,
+        register: i32,
+        size: i32,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        backoff::retry(self.backoff(), || {
+            self.i2c_read_register(address, register, size, timeout)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Send an I2C `request`, e.g. for register reads, 10-bit addresses or repeated starts.
+    fn retry_i2c_request(&mut self, request: &I2CRequest) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.i2c_request(request).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Stop reading continuously from the I2C device at `address`.
+    fn retry_i2c_stop_reading(&mut self, address: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.i2c_stop_reading(address)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
     /// Write `data` to the I2C device at the specified `address`.
     fn retry_i2c_write(&mut self, address: i32, data: &[u8]) -> Result<()> {
         backoff::retry(self.backoff(), || {
@@ -218,6 +732,76 @@ pub trait RetryFirmata: Firmata {
         })
         .map_err(|e| e.into())
     }
+    /// Group AccelStepper `devices` to move them in sync.
+    fn retry_multi_stepper_config(&mut self, group: i32, devices: &[i32]) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.multi_stepper_config(group, devices)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Stop the steppers of a `group`.
+    fn retry_multi_stepper_stop(&mut self, group: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.multi_stepper_stop(group)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Move the steppers of a `group` to absolute `positions`, in the order of the group, so they
+    /// all arrive at the same time.
+    fn retry_multi_stepper_to(&mut self, group: i32, positions: &[i32]) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.multi_stepper_to(group, positions)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Configure `pin` as a OneWire bus, with parasitic `power` after writes if set.
+    fn retry_onewire_config(&mut self, pin: i32, power: bool) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.onewire_config(pin, power)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Run a `request` on the OneWire bus at `pin`.
+    fn retry_onewire_request(&mut self, pin: i32, request: &OneWireRequest) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.onewire_request(pin, request)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Search the OneWire bus at `pin` and wait up to `timeout` for the addresses of its devices.
+    fn retry_onewire_search(&mut self, pin: i32, timeout: Duration) -> Result<Vec<OneWireAddress>> {
+        backoff::retry(self.backoff(), || {
+            self.onewire_search(pin, timeout)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Search the OneWire bus at `pin` and wait up to `timeout` for the addresses of the devices
+    /// in an alarm state.
+    fn retry_onewire_search_alarms(
+        &mut self,
+        pin: i32,
+        timeout: Duration,
+    ) -> Result<Vec<OneWireAddress>> {
+        backoff::retry(self.backoff(), || {
+            self.onewire_search_alarms(pin, timeout)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Query the board for the position of AccelStepper `device`.
+    fn retry_query_accel_stepper_position(&mut self, device: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.query_accel_stepper_position(device)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
     /// Query the board for available analog pins.
     fn retry_query_analog_mapping(&mut self) -> Result<()> {
         backoff::retry(self.backoff(), || {
@@ -240,14 +824,61 @@ pub trait RetryFirmata: Firmata {
         })
         .map_err(|e| e.into())
     }
-    /// Read from the Firmata device, parse one Firmata message and return its type.
+    /// Query the board for the position of `encoder`.
+    fn retry_query_encoder_position(&mut self, encoder: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.query_encoder_position(encoder)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Query the board for the positions of all encoders.
+    fn retry_query_encoder_positions(&mut self) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.query_encoder_positions()
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Query the board for the current mode and value of the specified `pin`.
+    fn retry_query_pin_state(&mut self, pin: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.query_pin_state(pin).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Query the board for the Firmata protocol version.
+    fn retry_query_protocol_version(&mut self) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.query_protocol_version()
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Query the board for the state of scheduler `task`.
+    fn retry_query_scheduler_task(&mut self, task: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.query_scheduler_task(task)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Query the board for the IDs of all scheduler tasks.
+    fn retry_query_scheduler_tasks(&mut self) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.query_scheduler_tasks()
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Read from the Firmata device, parse one Firmata message and return it.
     fn retry_read_and_decode(&mut self) -> Result<Message> {
         backoff::retry(self.backoff(), || {
             self.read_and_decode().map_err(backoff::Error::transient)
         })
         .map_err(|e| e.into())
     }
-    /// Set the analog reporting `state` of the specified `pin`.
+    /// Set the analog reporting `state` of the specified analog `pin`.
     fn retry_report_analog(&mut self, pin: i32, state: i32) -> Result<()> {
         backoff::retry(self.backoff(), || {
             self.report_analog(pin, state)
@@ -263,371 +894,2139 @@ pub trait RetryFirmata: Firmata {
         })
         .map_err(|e| e.into())
     }
-    /// Set the `mode` of the specified `pin`.
-    fn retry_set_pin_mode(&mut self, pin: i32, mode: u8) -> Result<()> {
+    /// Set the `state` of reporting the positions of all encoders at every sampling interval.
+    fn retry_report_encoders(&mut self, state: i32) -> Result<()> {
         backoff::retry(self.backoff(), || {
-            self.set_pin_mode(pin, mode)
+            self.report_encoders(state)
                 .map_err(backoff::Error::transient)
         })
         .map_err(|e| e.into())
     }
-}
-
-impl<T> RetryFirmata for T where T: Firmata {}
-
-/// A Firmata board representation.
-#[derive(Debug)]
-pub struct Board<T: Read + Write + std::fmt::Debug> {
-    pub connection: Box<T>,
-    pub pins: Vec<Pin>,
-    pub i2c_data: Vec<I2CReply>,
-    pub protocol_version: String,
-    pub firmware_name: String,
-    pub firmware_version: String,
-}
-impl<T: Read + Write + std::fmt::Debug> std::fmt::Display for Board<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Board {{ firmware={}, version={}, protocol={}, connection={:?} }}",
-            self.firmware_name, self.firmware_version, self.protocol_version, self.connection
-        )
-    }
-}
-impl<T: Read + Write + std::fmt::Debug> Board<T> {
-    /// Write on the internal connection.
-    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn write(&mut self, buf: &[u8]) -> Result<()> {
-        self.connection
-            .write(buf)
-            .map(|_| ())
-            .with_context(|_| StdIoSnafu)
+    /// Append `commands` to the body of scheduler `task`. Use [`Command::SchedulerDelayTask`] to
+    /// wait between commands.
+    fn retry_scheduler_add_to_task(&mut self, task: i32, commands: &[Command]) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.scheduler_add_to_task(task, commands)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
     }
-}
-
-impl<T: Read + Write + std::fmt::Debug> Board<T> {
-    fn initialize_board(&mut self) -> Result<()> {
-        self.query_firmware()?;
-        self.query_capabilities()?;
-        self.query_analog_mapping()?;
-
-        // Wait a little for the messages to queue up
-        std::thread::sleep(std::time::Duration::from_millis(1000));
-
-        let mut received_firmware = false;
-        let mut received_capabilities = false;
-        let mut received_analog_mapping = false;
-
-        while !received_firmware || !received_capabilities || !received_analog_mapping {
-            match self.read_and_decode() {
-                Ok(Message::ReportFirmware) => received_firmware = true,
-                Ok(Message::CapabilityResponse) => received_capabilities = true,
-                Ok(Message::AnalogMappingResponse) => received_analog_mapping = true,
-                Ok(_) => {} // Received some other message, continue waiting
-                Err(e) => return Err(e),
-            }
-        }
-
-        self.report_digital(0, 1)?;
-        self.report_digital(1, 1)?;
-
-        Ok(())
+    /// Create scheduler `task` with room for a body of `length` bytes, e.g. the total length of
+    /// the encoded commands it runs.
+    fn retry_scheduler_create_task(&mut self, task: i32, length: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.scheduler_create_task(task, length)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
     }
-    /// Creates a new `Board` given a `Read+Write`.
-    #[tracing::instrument(err, ret(Display))]
-    pub fn new(connection: Box<T>) -> Result<Board<T>> {
-        let mut b = Board {
-            connection,
-            firmware_name: String::new(),
-            firmware_version: String::new(),
-            protocol_version: String::new(),
-            pins: vec![],
-            i2c_data: vec![],
-        };
-        b.initialize_board()?;
-        Ok(b)
+    /// Create scheduler `task` with a body of `commands`, sized to fit them.
+    fn retry_scheduler_create_task_from(&mut self, task: i32, commands: &[Command]) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.scheduler_create_task_from(task, commands)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
     }
-    /// Tries to create a new `Board` given a `Read+Write`.
-    #[tracing::instrument(err, ret(Display))]
-    pub fn retry_new(connection: Box<T>) -> Result<Board<T>> {
-        let mut b = Board {
-            connection,
-            firmware_name: String::new(),
-            firmware_version: String::new(),
-            protocol_version: String::new(),
-            pins: vec![],
-            i2c_data: vec![],
-        };
-        b.initialize_board()?;
-        Ok(b)
+    /// Delete scheduler `task`.
+    fn retry_scheduler_delete_task(&mut self, task: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.scheduler_delete_task(task)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
     }
-}
-
-impl<T: Read + Write + std::fmt::Debug> Firmata for Board<T> {
-    fn pins(&mut self) -> &Vec<Pin> {
-        &self.pins
+    /// Delete all scheduler tasks.
+    fn retry_scheduler_reset(&mut self) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.scheduler_reset().map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
     }
-    fn protocol_version(&mut self) -> &String {
-        &self.protocol_version
+    /// Run scheduler `task` after `delay`.
+    fn retry_scheduler_schedule_task(&mut self, task: i32, delay: Duration) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.scheduler_schedule_task(task, delay)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Send a `string` to firmware that accepts text commands.
+    fn retry_send_string(&mut self, string: &str) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.send_string(string).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Close the serial `port`.
+    fn retry_serial_close(&mut self, port: SerialPortId) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.serial_close(port).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Configure the serial `port` with a `baud` rate. Software serial ports also need their RX
+    /// and TX `pins`.
+    fn retry_serial_config(
+        &mut self,
+        port: SerialPortId,
+        baud: i32,
+        pins: Option<(i32, i32)>,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.serial_config(port, baud, pins)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Flush the serial `port`.
+    fn retry_serial_flush(&mut self, port: SerialPortId) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.serial_flush(port).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Listen on the software serial `port`, only one software serial port can listen at a time.
+    fn retry_serial_listen(&mut self, port: SerialPortId) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.serial_listen(port).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Read continuously from the serial `port`, at most `max_bytes` per report if set.
+    fn retry_serial_read_continuously(
+        &mut self,
+        port: SerialPortId,
+        max_bytes: Option<i32>,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.serial_read_continuously(port, max_bytes)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Stop reading from the serial `port`.
+    fn retry_serial_stop_reading(&mut self, port: SerialPortId) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.serial_stop_reading(port)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Write `data` to the serial `port`.
+    fn retry_serial_write(&mut self, port: SerialPortId, data: &[u8]) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.serial_write(port, data)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Attach a servo to `pin`, with the pulse widths in microseconds at 0 and 180 degrees.
+    fn retry_servo_config(&mut self, pin: i32, min_pulse: i32, max_pulse: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.servo_config(pin, min_pulse, max_pulse)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Turn the servo on `pin` to an angle between 0 and 180 `degrees`.
+    fn retry_servo_write_degrees(&mut self, pin: i32, degrees: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.servo_write_degrees(pin, degrees)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Send a pulse width in `micros` to the servo on `pin`.
+    fn retry_servo_write_micros(&mut self, pin: i32, micros: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.servo_write_micros(pin, micros)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Write `level` to the digital `pin` using a single pin message.
+    fn retry_set_digital_pin_value(&mut self, pin: i32, level: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.set_digital_pin_value(pin, level)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Set the `mode` of the specified `pin`, if the pin supports it.
+    fn retry_set_pin_mode(&mut self, pin: i32, mode: PinMode) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.set_pin_mode(pin, mode)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Set the `interval` at which the board samples and reports analog and I2C data.
+    fn retry_set_sampling_interval(&mut self, interval: Duration) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.set_sampling_interval(interval)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Shift `size` bytes into `data_pin`, clocked by `clock_pin`, and wait up to `timeout` for
+    /// them.
+    fn retry_shift_in(
+        &mut self,
+        data_pin: i32,
+        clock_pin: i32,
+        bit_order: BitOrder,
+        size: i32,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        backoff::retry(self.backoff(), || {
+            self.shift_in(data_pin, clock_pin, bit_order, size, timeout)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Shift `data` out of `data_pin`, clocked by `clock_pin`, e.g. into a chain of 74HC595s.
+    fn retry_shift_out(
+        &mut self,
+        data_pin: i32,
+        clock_pin: i32,
+        bit_order: BitOrder,
+        data: &[u8],
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.shift_out(data_pin, clock_pin, bit_order, data)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Initialize SPI `channel`.
+    fn retry_spi_begin(&mut self, channel: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.spi_begin(channel).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Configure SPI `device`, e.g. its mode, clock speed and chip select pin.
+    fn retry_spi_device_config(
+        &mut self,
+        device: SpiDevice,
+        config: SpiDeviceConfig,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.spi_device_config(device, config)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Release SPI `channel`.
+    fn retry_spi_end(&mut self, channel: i32) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.spi_end(channel).map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Read `size` words from SPI `device` and wait up to `timeout` for them. The chip select pin is
+    /// deselected afterwards if `deselect` is set.
+    fn retry_spi_read(
+        &mut self,
+        device: SpiDevice,
+        size: i32,
+        deselect: bool,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        backoff::retry(self.backoff(), || {
+            self.spi_read(device, size, deselect, timeout)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Write `data` to SPI `device` and wait up to `timeout` for the words it sends back. The chip
+    /// select pin is deselected afterwards if `deselect` is set.
+    fn retry_spi_transfer(
+        &mut self,
+        device: SpiDevice,
+        data: &[u8],
+        deselect: bool,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        backoff::retry(self.backoff(), || {
+            self.spi_transfer(device, data, deselect, timeout)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Write `data` to SPI `device`. The chip select pin is deselected afterwards if `deselect` is
+    /// set.
+    fn retry_spi_write(&mut self, device: SpiDevice, data: &[u8], deselect: bool) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.spi_write(device, data, deselect)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Configure stepper `device` with the number of steps per revolution and how it is wired.
+    fn retry_stepper_config(
+        &mut self,
+        device: i32,
+        steps_per_revolution: i32,
+        interface: StepperInterface,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.stepper_config(device, steps_per_revolution, interface)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Move stepper `device` a number of `steps`, counterclockwise if negative, at `speed` in
+    /// 0.01 rad/s, with an optional acceleration and deceleration `ramp`.
+    fn retry_stepper_step(
+        &mut self,
+        device: i32,
+        steps: i32,
+        speed: i32,
+        ramp: Option<StepperRamp>,
+    ) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.stepper_step(device, steps, speed, ramp)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+    /// Reset the board and the cached pin and I2C state. Optionally `reinitialize` by querying
+    /// the board's firmware and capabilities again.
+    fn retry_system_reset(&mut self, reinitialize: bool) -> Result<()> {
+        backoff::retry(self.backoff(), || {
+            self.system_reset(reinitialize)
+                .map_err(backoff::Error::transient)
+        })
+        .map_err(|e| e.into())
+    }
+}
+
+impl<T> RetryFirmata for T where T: Firmata {}
+
+/// A Firmata board representation.
+#[derive(Debug)]
+pub struct Board<T: Read + Write + std::fmt::Debug> {
+    pub connection: Box<T>,
+    pub decoder: Decoder,
+    pub pins: Vec<Pin>,
+    pub capabilities: Capabilities,
+    pub analog_mapping: Vec<Option<u8>>,
+    pub i2c_data: Vec<I2CReply>,
+    /// Messages that were read while waiting for another message, returned first by
+    /// `read_and_decode`. Nothing else drains it, so it grows until `read_and_decode` is called.
+    pub pending: VecDeque<Message>,
+    /// Configuration of each AccelStepper, by device number.
+    pub accel_steppers: HashMap<u8, AccelStepperConfig>,
+    /// Last reported position of each AccelStepper.
+    pub accel_stepper_positions: HashMap<u8, i32>,
+    /// AccelStepper devices of each multi-stepper group.
+    pub multi_steppers: HashMap<u8, Vec<u8>>,
+    /// Wiring of each configured stepper, by device number.
+    pub steppers: HashMap<u8, StepperInterface>,
+    /// Last reported position of each encoder.
+    pub encoder_positions: HashMap<u8, i32>,
+    /// Bytes read from OneWire buses by correlation ID.
+    pub onewire_data: HashMap<u16, Vec<u8>>,
+    /// Data read from each serial port that has not been consumed yet.
+    pub serial_data: HashMap<SerialPortId, VecDeque<u8>>,
+    /// Words read from SPI devices by request ID that have not been consumed yet.
+    pub spi_data: HashMap<u8, Vec<u8>>,
+    /// Request ID of the next SPI transfer, write or read.
+    pub spi_request_id: u8,
+    pub protocol_version: Option<ProtocolVersion>,
+    pub firmware_name: String,
+    pub firmware_version: Option<FirmwareVersion>,
+    pub sampling_interval: Duration,
+}
+impl<T: Read + Write + std::fmt::Debug> std::fmt::Display for Board<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = || "?".to_string();
+        write!(
+            f,
+            "Board {{ firmware={}, version={}, protocol={}, connection={:?} }}",
+            self.firmware_name,
+            self.firmware_version
+                .map_or_else(unknown, |version| version.to_string()),
+            self.protocol_version
+                .map_or_else(unknown, |version| version.to_string()),
+            self.connection
+        )
+    }
+}
+impl<T: Read + Write + std::fmt::Debug> Board<T> {
+    /// Write on the internal connection.
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn write(&mut self, buf: &[u8]) -> Result<()> {
+        self.connection
+            .write(buf)
+            .map(|_| ())
+            .with_context(|_| StdIoSnafu)
+    }
+    /// Encode and send a `command` on the internal connection.
+    fn send(&mut self, command: &Command) -> Result<()> {
+        self.write(&command.encode()?)
+    }
+    /// Get the state of a `pin`.
+    fn pin_mut(&mut self, pin: i32) -> Result<&mut Pin> {
+        let len = self.pins.len();
+        self.pins
+            .get_mut(pin as usize)
+            .with_context(|| PinOutOfBoundsSnafu {
+                pin: pin as u8,
+                len,
+            })
+    }
+    /// Update the current `mode` of a `pin` and its resolution in that mode.
+    fn update_mode(&mut self, pin: u8, mode: PinMode) {
+        let resolution = self.capabilities.resolution(pin, mode);
+        if let Some(p) = self.pins.get_mut(pin as usize) {
+            p.mode = mode;
+            if let Some(resolution) = resolution {
+                p.resolution = resolution;
+            }
+        }
+    }
+    /// Update the current mode of a `pin` to the one the firmware sets after a reset: analog
+    /// pins are analog inputs and all other pins digital outputs.
+    fn reset_mode(&mut self, pin: u8) {
+        let capabilities = &self.capabilities;
+        let mode = [PinMode::Analog, PinMode::Output]
+            .into_iter()
+            .chain(capabilities.modes(pin).iter().map(|c| c.mode))
+            .find(|&mode| capabilities.supports(pin, mode))
+            .unwrap_or(PinMode::Ignore);
+        self.update_mode(pin, mode);
+    }
+    /// Check whether the reported protocol version supports a `feature`.
+    ///
+    /// Only warns if the board did not report its protocol version.
+    fn require(&self, feature: Feature) -> Result<()> {
+        let required = feature.since();
+        match self.protocol_version {
+            Some(version) if version < required => Err(Error::UnsupportedFeature {
+                feature,
+                required,
+                version,
+            }),
+            Some(_) => Ok(()),
+            None => {
+                tracing::warn!("unknown protocol version, assuming {feature} is supported");
+                Ok(())
+            }
+        }
+    }
+    /// Read from the connection until the decoder yields a message, and apply it to the board
+    /// state.
+    fn read_message(&mut self) -> Result<Message> {
+        loop {
+            if let Some(message) = self.decoder.decode()? {
+                self.update(&message);
+                return Ok(message);
+            }
+            let mut buf = [0; 64];
+            let n = match self.connection.read(&mut buf) {
+                Ok(0) => {
+                    return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+                        .with_context(|_| StdIoSnafu)
+                }
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e).with_context(|_| StdIoSnafu),
+            };
+            self.decoder.feed(&buf[..n]);
+        }
+    }
+    /// Like `read_message`, but log and skip frames that fail to decode, e.g. from firmware
+    /// features the crate does not know, so only I/O errors are returned.
+    fn read_valid_message(&mut self) -> Result<Message> {
+        loop {
+            match self.read_message() {
+                Ok(message) => return Ok(message),
+                Err(e @ Error::StdIoError { .. }) => return Err(e),
+                Err(e) => tracing::warn!("skipped a message that failed to decode: {e}"),
+            }
+        }
+    }
+    /// Read messages until one matches `predicate`, for at most `timeout`.
+    ///
+    /// Other messages are kept in `pending` so `read_and_decode` still returns them, and frames
+    /// that fail to decode are skipped. The deadline is only checked between reads, so a read
+    /// that blocks overruns `timeout` by up to the read timeout of the connection, and without
+    /// one this never times out.
+    fn wait_for(
+        &mut self,
+        timeout: Duration,
+        predicate: impl Fn(&Message) -> bool,
+    ) -> Result<Message> {
+        if let Some(i) = self.pending.iter().position(&predicate) {
+            return Ok(self.pending.remove(i).expect("pending message"));
+        }
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            match self.read_valid_message() {
+                Ok(message) if predicate(&message) => return Ok(message),
+                Ok(message) => self.pending.push_back(message),
+                Err(Error::StdIoError { source })
+                    if matches!(
+                        source.kind(),
+                        std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                    ) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Err(Error::Timeout { timeout })
+    }
+    /// Wait up to `timeout` for the reply to a OneWire (`alarms`) search on `pin`.
+    fn wait_for_onewire_search(
+        &mut self,
+        pin: u8,
+        alarms: bool,
+        timeout: Duration,
+    ) -> Result<Vec<OneWireAddress>> {
+        let message = self.wait_for(timeout, |message| {
+            matches!(message, Message::OneWireSearchReply { pin: p, alarms: a, .. }
+                if *p == pin && *a == alarms)
+        })?;
+        let Message::OneWireSearchReply { addresses, .. } = message else {
+            unreachable!("waited for a OneWire search reply");
+        };
+        Ok(addresses)
+    }
+    /// Take the next SPI request ID, wrapping around after 127.
+    fn next_spi_request_id(&mut self) -> u8 {
+        let request_id = self.spi_request_id;
+        self.spi_request_id = (request_id + 1) & SYSEX_REALTIME;
+        // Forget an unconsumed reply to an earlier request with the same ID.
+        self.spi_data.remove(&request_id);
+        request_id
+    }
+    /// Wait up to `timeout` for the reply to the SPI request with `request_id`.
+    fn wait_for_spi_reply(&mut self, request_id: u8, timeout: Duration) -> Result<Vec<u8>> {
+        let message = self.wait_for(timeout, |message| {
+            matches!(message, Message::SpiReply { request_id: id, .. } if *id == request_id)
+        })?;
+        let Message::SpiReply { data, .. } = message else {
+            unreachable!("waited for an SPI reply");
+        };
+        // The reply is returned here, so take it out of the shared SPI data.
+        self.spi_data.remove(&request_id);
+        Ok(data)
+    }
+    /// Update the board state with a decoded `message`.
+    fn update(&mut self, message: &Message) {
+        match message {
+            Message::ProtocolVersion(version) => {
+                self.protocol_version = Some(*version);
+            }
+            Message::Analog { channel, value } => {
+                if let Some(pin) = self.pin_for_channel(*channel) {
+                    if let Some(pin) = self.pins.get_mut(pin as usize) {
+                        pin.value = *value;
+                    }
+                }
+            }
+            Message::Digital { port, mask } => {
+                for i in 0..8 {
+                    let pin = 8 * *port as usize + i;
+                    if let Some(pin) = self.pins.get_mut(pin) {
+                        if pin.mode == PinMode::Input {
+                            pin.value = ((mask >> i) & 0x01) as i32;
+                        }
+                    }
+                }
+            }
+            Message::EmptyResponse => {}
+            Message::AnalogMappingResponse(mapping) => {
+                for (pin, channel) in mapping.iter().enumerate() {
+                    if channel.is_some() {
+                        self.update_mode(pin as u8, PinMode::Analog);
+                    }
+                }
+                self.analog_mapping = mapping.clone();
+            }
+            Message::CapabilityResponse(capabilities) => {
+                self.capabilities = capabilities.clone();
+                self.pins = capabilities
+                    .pins
+                    .iter()
+                    .map(|modes| Pin {
+                        mode: PinMode::Ignore,
+                        resolution: 0,
+                        modes: modes.iter().map(|capability| capability.mode).collect(),
+                        value: 0,
+                    })
+                    .collect();
+                for pin in 0..self.pins.len() {
+                    self.reset_mode(pin as u8);
+                }
+            }
+            Message::PinStateResponse { pin, mode, value } => {
+                if let Some(mode) = mode {
+                    self.update_mode(*pin, *mode);
+                    if let Some(pin) = self.pins.get_mut(*pin as usize) {
+                        pin.value = *value;
+                    }
+                }
+            }
+            Message::ReportFirmware { version, name } => {
+                self.firmware_version = Some(*version);
+                if !name.is_empty() {
+                    self.firmware_name = name.clone();
+                }
+            }
+            Message::I2CReply(reply) => {
+                self.i2c_data.push(reply.clone());
+            }
+            Message::StringData(string) => {
+                tracing::info!("firmware: {string}");
+            }
+            Message::AccelStepperPosition { device, position }
+            | Message::AccelStepperMoveComplete { device, position } => {
+                self.accel_stepper_positions.insert(*device, *position);
+            }
+            Message::MultiStepperMoveComplete { .. } => {}
+            Message::EncoderPositions(positions) => {
+                for position in positions {
+                    self.encoder_positions
+                        .insert(position.encoder, position.position);
+                }
+            }
+            Message::OneWireSearchReply { .. } => {}
+            Message::OneWireReadReply {
+                correlation_id,
+                data,
+                ..
+            } => {
+                self.onewire_data.insert(*correlation_id, data.clone());
+            }
+            Message::SchedulerTasks(_)
+            | Message::SchedulerTaskReply { .. }
+            | Message::SchedulerTaskError { .. } => {}
+            Message::ShiftInReply { .. } => {}
+            Message::SpiReply {
+                request_id, data, ..
+            } => {
+                self.spi_data.insert(*request_id, data.clone());
+            }
+            Message::StepperMoveComplete { .. } => {}
+            Message::SerialReply { port, data } => {
+                self.serial_data.entry(*port).or_default().extend(data);
+            }
+        }
+    }
+}
+
+impl<T: Read + Write + std::fmt::Debug> Board<T> {
+    fn initialize_board(&mut self) -> Result<()> {
+        self.query_protocol_version()?;
+        self.query_firmware()?;
+        self.query_capabilities()?;
+        self.query_analog_mapping()?;
+
+        // Wait a little for the messages to queue up
+        std::thread::sleep(std::time::Duration::from_millis(1000));
+
+        let mut received_protocol_version = false;
+        let mut received_firmware = false;
+        let mut received_capabilities = false;
+        let mut received_analog_mapping = false;
+
+        while !received_protocol_version
+            || !received_firmware
+            || !received_capabilities
+            || !received_analog_mapping
+        {
+            match self.read_and_decode() {
+                Ok(Message::ProtocolVersion(_)) => received_protocol_version = true,
+                Ok(Message::ReportFirmware { .. }) => received_firmware = true,
+                Ok(Message::CapabilityResponse(_)) => received_capabilities = true,
+                Ok(Message::AnalogMappingResponse(_)) => received_analog_mapping = true,
+                Ok(_) => {} // Received some other message, continue waiting
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.report_digital(0, 1)?;
+        self.report_digital(1, 1)?;
+
+        Ok(())
+    }
+    /// Wraps `connection` in a `Board` with no known state yet.
+    fn with_connection(connection: Box<T>) -> Self {
+        Board {
+            connection,
+            decoder: Decoder::new(),
+            firmware_name: String::new(),
+            firmware_version: None,
+            protocol_version: None,
+            pins: vec![],
+            capabilities: Capabilities::default(),
+            analog_mapping: vec![],
+            i2c_data: vec![],
+            pending: VecDeque::new(),
+            accel_steppers: HashMap::new(),
+            accel_stepper_positions: HashMap::new(),
+            multi_steppers: HashMap::new(),
+            steppers: HashMap::new(),
+            encoder_positions: HashMap::new(),
+            onewire_data: HashMap::new(),
+            serial_data: HashMap::new(),
+            spi_data: HashMap::new(),
+            spi_request_id: 0,
+            sampling_interval: Duration::from_millis(DEFAULT_SAMPLING_INTERVAL as u64),
+        }
+    }
+    /// Creates a new `Board` given a `Read+Write`.
+    #[tracing::instrument(err, ret(Display))]
+    pub fn new(connection: Box<T>) -> Result<Board<T>> {
+        let mut b = Board::with_connection(connection);
+        b.initialize_board()?;
+        Ok(b)
+    }
+    /// Tries to create a new `Board` given a `Read+Write`.
+    #[tracing::instrument(err, ret(Display))]
+    pub fn retry_new(connection: Box<T>) -> Result<Board<T>> {
+        let mut b = Board::with_connection(connection);
+        b.initialize_board()?;
+        Ok(b)
+    }
+    /// Get a handle to read and write the serial `port` of the board.
+    pub fn serial(&mut self, port: SerialPortId) -> Serial<'_, T> {
+        Serial::new(self, port)
+    }
+    /// Get a handle to move stepper `device` of the board.
+    pub fn stepper(&mut self, device: u8) -> Stepper<'_, T> {
+        Stepper::new(self, device)
+    }
+}
+
+impl<T: Read + Write + std::fmt::Debug> Firmata for Board<T> {
+    fn analog_channel(&mut self, pin: i32) -> Option<u8> {
+        usize::try_from(pin)
+            .ok()
+            .and_then(|pin| self.analog_mapping.get(pin).copied().flatten())
+    }
+    fn pin_for_channel(&mut self, channel: u8) -> Option<i32> {
+        self.analog_mapping
+            .iter()
+            .position(|&c| c == Some(channel))
+            .map(|pin| pin as i32)
+    }
+    fn pins(&mut self) -> &Vec<Pin> {
+        &self.pins
+    }
+    fn capabilities(&mut self) -> &Capabilities {
+        &self.capabilities
+    }
+    fn protocol_version(&mut self) -> Option<ProtocolVersion> {
+        self.protocol_version
     }
     fn firmware_name(&mut self) -> &String {
         &self.firmware_name
     }
-    fn firmware_version(&mut self) -> &String {
-        &self.firmware_version
+    fn firmware_version(&mut self) -> Option<FirmwareVersion> {
+        self.firmware_version
+    }
+    fn i2c_data(&mut self) -> &mut Vec<I2CReply> {
+        &mut self.i2c_data
+    }
+    fn accel_stepper_positions(&mut self) -> &HashMap<u8, i32> {
+        &self.accel_stepper_positions
+    }
+    fn encoder_positions(&mut self) -> &HashMap<u8, i32> {
+        &self.encoder_positions
+    }
+    fn onewire_data(&mut self) -> &mut HashMap<u16, Vec<u8>> {
+        &mut self.onewire_data
+    }
+    fn serial_data(&mut self) -> &mut HashMap<SerialPortId, VecDeque<u8>> {
+        &mut self.serial_data
+    }
+    fn steppers(&mut self) -> &HashMap<u8, StepperInterface> {
+        &self.steppers
+    }
+    fn sampling_interval(&mut self) -> Duration {
+        self.sampling_interval
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_accel_stepper_position(&mut self, device: i32) -> Result<()> {
+        self.send(&Command::AccelStepperReportPosition {
+            device: device as u8,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_analog_mapping(&mut self) -> Result<()> {
+        self.send(&Command::AnalogMappingQuery)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_capabilities(&mut self) -> Result<()> {
+        self.send(&Command::CapabilityQuery)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_firmware(&mut self) -> Result<()> {
+        self.send(&Command::ReportFirmware)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_pin_state(&mut self, pin: i32) -> Result<()> {
+        self.send(&Command::PinStateQuery { pin: pin as u8 })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_encoder_position(&mut self, encoder: i32) -> Result<()> {
+        self.send(&Command::EncoderReportPosition {
+            encoder: encoder as u8,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_encoder_positions(&mut self) -> Result<()> {
+        self.send(&Command::EncoderReportPositions)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_protocol_version(&mut self) -> Result<()> {
+        self.send(&Command::ReportVersion)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_scheduler_task(&mut self, task: i32) -> Result<()> {
+        self.send(&Command::SchedulerQueryTask { task: task as u8 })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn query_scheduler_tasks(&mut self) -> Result<()> {
+        self.send(&Command::SchedulerQueryAllTasks)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn i2c_config(&mut self, delay: i32) -> Result<()> {
+        self.send(&Command::I2CConfig {
+            delay: delay as u16,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn i2c_read(&mut self, address: i32, size: i32) -> Result<()> {
+        self.i2c_request(&I2CRequest::new(
+            address as u16,
+            I2COperation::Read {
+                register: None,
+                size: size as u16,
+            },
+        ))
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn i2c_read_continuously(
+        &mut self,
+        address: i32,
+        register: Option<i32>,
+        size: i32,
+    ) -> Result<()> {
+        self.i2c_request(&I2CRequest::new(
+            address as u16,
+            I2COperation::ReadContinuously {
+                register: register.map(|register| register as u16),
+                size: size as u16,
+            },
+        ))
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn i2c_read_register(
+        &mut self,
+        address: i32,
+        register: i32,
+        size: i32,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        self.i2c_request(&I2CRequest::new(
+            address as u16,
+            I2COperation::Read {
+                register: Some(register as u16),
+                size: size as u16,
+            },
+        ))?;
+        let message = self.wait_for(timeout, |message| {
+            matches!(message, Message::I2CReply(reply)
+                if reply.address == address && reply.register == register)
+        })?;
+        let Message::I2CReply(reply) = message else {
+            unreachable!("waited for an I2C reply");
+        };
+        // The reply is returned here, so take it out of the shared I2C data.
+        if let Some(i) = self.i2c_data.iter().rposition(|r| *r == reply) {
+            self.i2c_data.remove(i);
+        }
+        Ok(reply.data)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn i2c_request(&mut self, request: &I2CRequest) -> Result<()> {
+        self.send(&Command::I2CRequest(request.clone()))
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn i2c_stop_reading(&mut self, address: i32) -> Result<()> {
+        self.i2c_request(&I2CRequest::new(address as u16, I2COperation::StopReading))
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn i2c_write(&mut self, address: i32, data: &[u8]) -> Result<()> {
+        self.i2c_request(&I2CRequest::new(
+            address as u16,
+            I2COperation::Write {
+                data: data.to_vec(),
+            },
+        ))
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn report_digital(&mut self, pin: i32, state: i32) -> Result<()> {
+        self.send(&Command::ReportDigital {
+            port: pin as u8,
+            enable: state != 0,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn report_encoders(&mut self, state: i32) -> Result<()> {
+        self.send(&Command::EncoderReportAuto { enable: state != 0 })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn report_analog(&mut self, pin: i32, state: i32) -> Result<()> {
+        let channel = self
+            .analog_channel(pin)
+            .with_context(|| NotAnalogSnafu { pin: pin as u8 })?;
+        self.send(&Command::ReportAnalog {
+            channel,
+            enable: state != 0,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn analog_write(&mut self, pin: i32, level: i32) -> Result<()> {
+        ensure!(level >= 0, InvalidAnalogLevelSnafu { level });
+        let resolution = self.pin_mut(pin)?.resolution;
+        if pin > 15 || !(0..=0x3FFF).contains(&level) || resolution > 14 {
+            return self.extended_analog(pin, level);
+        }
+        self.pin_mut(pin)?.value = level;
+        self.send(&Command::AnalogWrite {
+            pin: pin as u8,
+            value: level as u16,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_config(&mut self, device: i32, config: AccelStepperConfig) -> Result<()> {
+        for pin in config.interface.pins() {
+            ensure!(
+                self.capabilities.supports(pin, PinMode::Stepper),
+                UnsupportedPinModeSnafu {
+                    pin,
+                    mode: PinMode::Stepper
+                }
+            );
+        }
+        self.send(&Command::AccelStepperConfig {
+            device: device as u8,
+            config,
+        })?;
+        // The firmware switches the motor pins to stepper mode, drives the enable pin as an output
+        // and starts at position zero.
+        for pin in config.interface.pins() {
+            self.update_mode(pin, PinMode::Stepper);
+        }
+        if let Some(enable_pin) = config.enable_pin {
+            self.update_mode(enable_pin, PinMode::Output);
+        }
+        self.accel_steppers.insert(device as u8, config);
+        self.accel_stepper_positions.insert(device as u8, 0);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_enable(&mut self, device: i32, enable: bool) -> Result<()> {
+        self.send(&Command::AccelStepperEnable {
+            device: device as u8,
+            enable,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_move(&mut self, device: i32, steps: i32) -> Result<()> {
+        self.send(&Command::AccelStepperStep {
+            device: device as u8,
+            steps,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_set_acceleration(&mut self, device: i32, acceleration: f32) -> Result<()> {
+        self.send(&Command::AccelStepperSetAcceleration {
+            device: device as u8,
+            acceleration,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_set_speed(&mut self, device: i32, speed: f32) -> Result<()> {
+        self.send(&Command::AccelStepperSetSpeed {
+            device: device as u8,
+            speed,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_stop(&mut self, device: i32) -> Result<()> {
+        self.send(&Command::AccelStepperStop {
+            device: device as u8,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_to(&mut self, device: i32, position: i32) -> Result<()> {
+        self.send(&Command::AccelStepperTo {
+            device: device as u8,
+            position,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn accel_stepper_zero(&mut self, device: i32) -> Result<()> {
+        self.send(&Command::AccelStepperZero {
+            device: device as u8,
+        })?;
+        self.accel_stepper_positions.insert(device as u8, 0);
+        Ok(())
     }
-    fn i2c_data(&mut self) -> &mut Vec<I2CReply> {
-        &mut self.i2c_data
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn multi_stepper_config(&mut self, group: i32, devices: &[i32]) -> Result<()> {
+        let devices: Vec<u8> = devices.iter().map(|&device| device as u8).collect();
+        self.send(&Command::MultiStepperConfig {
+            group: group as u8,
+            devices: devices.clone(),
+        })?;
+        self.multi_steppers.insert(group as u8, devices);
+        Ok(())
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn query_analog_mapping(&mut self) -> Result<()> {
-        self.write(&[START_SYSEX, ANALOG_MAPPING_QUERY, END_SYSEX])
+    fn multi_stepper_stop(&mut self, group: i32) -> Result<()> {
+        self.send(&Command::MultiStepperStop { group: group as u8 })
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn query_capabilities(&mut self) -> Result<()> {
-        self.write(&[START_SYSEX, CAPABILITY_QUERY, END_SYSEX])
+    fn multi_stepper_to(&mut self, group: i32, positions: &[i32]) -> Result<()> {
+        if let Some(devices) = self.multi_steppers.get(&(group as u8)) {
+            ensure!(
+                devices.len() == positions.len(),
+                InvalidMultiStepperPositionsSnafu {
+                    group: group as u8,
+                    expected: devices.len(),
+                    len: positions.len(),
+                }
+            );
+        }
+        self.send(&Command::MultiStepperTo {
+            group: group as u8,
+            positions: positions.to_vec(),
+        })
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn query_firmware(&mut self) -> Result<()> {
-        self.write(&[START_SYSEX, REPORT_FIRMWARE, END_SYSEX])
+    fn encoder_attach(&mut self, encoder: i32, pin_a: i32, pin_b: i32) -> Result<()> {
+        for pin in [pin_a, pin_b] {
+            ensure!(
+                self.capabilities.supports(pin as u8, PinMode::Encoder),
+                UnsupportedPinModeSnafu {
+                    pin: pin as u8,
+                    mode: PinMode::Encoder
+                }
+            );
+        }
+        self.send(&Command::EncoderAttach {
+            encoder: encoder as u8,
+            pin_a: pin_a as u8,
+            pin_b: pin_b as u8,
+        })?;
+        // The firmware switches both pins to encoder mode and starts counting from zero.
+        self.update_mode(pin_a as u8, PinMode::Encoder);
+        self.update_mode(pin_b as u8, PinMode::Encoder);
+        self.encoder_positions.insert(encoder as u8, 0);
+        Ok(())
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn i2c_config(&mut self, delay: i32) -> Result<()> {
-        self.write(&[
-            START_SYSEX,
-            I2C_CONFIG,
-            (delay & 0xFF) as u8,
-            (delay >> 8 & 0xFF) as u8,
-            END_SYSEX,
-        ])
+    fn encoder_detach(&mut self, encoder: i32) -> Result<()> {
+        self.send(&Command::EncoderDetach {
+            encoder: encoder as u8,
+        })?;
+        self.encoder_positions.remove(&(encoder as u8));
+        Ok(())
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn i2c_read(&mut self, address: i32, size: i32) -> Result<()> {
-        self.write(&[
-            START_SYSEX,
-            I2C_REQUEST,
-            address as u8,
-            I2C_READ << 3,
-            (size as u8) & SYSEX_REALTIME,
-            (size >> 7) as u8 & SYSEX_REALTIME,
-            END_SYSEX,
-        ])
+    fn encoder_reset_position(&mut self, encoder: i32) -> Result<()> {
+        self.send(&Command::EncoderResetPosition {
+            encoder: encoder as u8,
+        })?;
+        self.encoder_positions.insert(encoder as u8, 0);
+        Ok(())
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn i2c_write(&mut self, address: i32, data: &[u8]) -> Result<()> {
-        let mut buf = vec![START_SYSEX, I2C_REQUEST, address as u8, I2C_WRITE << 3];
+    fn extended_analog(&mut self, pin: i32, level: i32) -> Result<()> {
+        ensure!(level >= 0, InvalidAnalogLevelSnafu { level });
+        self.pin_mut(pin)?.value = level;
+        self.send(&Command::ExtendedAnalog {
+            pin: pin as u8,
+            value: level as u32,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn digital_write(&mut self, pin: i32, level: i32) -> Result<()> {
+        let supported = Feature::SetDigitalPinValue.since();
+        if self
+            .protocol_version
+            .is_some_and(|version| version >= supported)
+        {
+            return self.set_digital_pin_value(pin, level);
+        }
+
+        self.pin_mut(pin)?.value = level;
 
-        for i in data.iter() {
-            buf.push(i & SYSEX_REALTIME);
-            buf.push(((*i as i32) >> 7) as u8 & SYSEX_REALTIME);
+        // Older firmware only accepts whole ports, so only include the output pins of the port to
+        // leave the other pins alone.
+        let port = pin as usize / 8;
+        let mut mask = 0u8;
+        for i in 0..8 {
+            let index = 8 * port + i;
+            if let Some(p) = self.pins.get(index) {
+                let output = index == pin as usize || p.mode == PinMode::Output;
+                if output && p.value != 0 {
+                    mask |= 1 << i;
+                }
+            }
         }
 
-        buf.push(END_SYSEX);
+        self.send(&Command::DigitalWrite {
+            port: port as u8,
+            mask,
+        })
+    }
 
-        self.write(&buf)
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn scheduler_add_to_task(&mut self, task: i32, commands: &[Command]) -> Result<()> {
+        let mut data = vec![];
+        for command in commands {
+            data.extend(command.encode()?);
+        }
+        for chunk in data.chunks(SCHEDULER_ADD_TO_TASK_MAX_BYTES) {
+            self.send(&Command::SchedulerAddToTask {
+                task: task as u8,
+                data: chunk.to_vec(),
+            })?;
+        }
+        Ok(())
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn report_digital(&mut self, pin: i32, state: i32) -> Result<()> {
-        self.write(&[REPORT_DIGITAL | pin as u8, state as u8])
+    fn scheduler_create_task(&mut self, task: i32, length: i32) -> Result<()> {
+        ensure!(
+            (1..=0x3FFF).contains(&length),
+            InvalidSchedulerTaskLengthSnafu { length }
+        );
+        self.send(&Command::SchedulerCreateTask {
+            task: task as u8,
+            length: length as u16,
+        })
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn report_analog(&mut self, pin: i32, state: i32) -> Result<()> {
-        self.write(&[REPORT_ANALOG | pin as u8, state as u8])
+    fn scheduler_create_task_from(&mut self, task: i32, commands: &[Command]) -> Result<()> {
+        let mut length = 0;
+        for command in commands {
+            length += command.encode()?.len();
+        }
+        self.scheduler_create_task(task, length.try_into().unwrap_or(i32::MAX))?;
+        self.scheduler_add_to_task(task, commands)
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn analog_write(&mut self, pin: i32, level: i32) -> Result<()> {
-        self.pins[pin as usize].value = level;
-        self.write(&[
-            ANALOG_MESSAGE | pin as u8,
-            level as u8 & SYSEX_REALTIME,
-            (level >> 7) as u8 & SYSEX_REALTIME,
-        ])
+    fn scheduler_delete_task(&mut self, task: i32) -> Result<()> {
+        self.send(&Command::SchedulerDeleteTask { task: task as u8 })
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn digital_write(&mut self, pin: i32, level: i32) -> Result<()> {
-        let port = (pin as f64 / 8f64).floor() as usize;
-        let mut value = 0i32;
-        let mut i = 0;
+    fn scheduler_reset(&mut self) -> Result<()> {
+        self.send(&Command::SchedulerReset)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn scheduler_schedule_task(&mut self, task: i32, delay: Duration) -> Result<()> {
+        let millis = u32::try_from(delay.as_millis())
+            .ok()
+            .with_context(|| InvalidSchedulerDelaySnafu { delay })?;
+        self.send(&Command::SchedulerScheduleTask {
+            task: task as u8,
+            delay: millis,
+        })
+    }
 
-        self.pins[pin as usize].value = level;
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn send_string(&mut self, string: &str) -> Result<()> {
+        self.send(&Command::StringData(string.to_string()))
+    }
 
-        while i < 8 {
-            if self.pins[8 * port + i].value != 0 {
-                value |= 1 << i
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn onewire_config(&mut self, pin: i32, power: bool) -> Result<()> {
+        ensure!(
+            self.capabilities.supports(pin as u8, PinMode::OneWire),
+            UnsupportedPinModeSnafu {
+                pin: pin as u8,
+                mode: PinMode::OneWire
             }
-            i += 1;
+        );
+        self.send(&Command::OneWireConfig {
+            pin: pin as u8,
+            power,
+        })?;
+        // The firmware switches the pin to OneWire mode.
+        self.update_mode(pin as u8, PinMode::OneWire);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn onewire_request(&mut self, pin: i32, request: &OneWireRequest) -> Result<()> {
+        self.send(&Command::OneWireRequest {
+            pin: pin as u8,
+            request: request.clone(),
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn onewire_search(&mut self, pin: i32, timeout: Duration) -> Result<Vec<OneWireAddress>> {
+        self.send(&Command::OneWireSearch { pin: pin as u8 })?;
+        self.wait_for_onewire_search(pin as u8, false, timeout)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn onewire_search_alarms(
+        &mut self,
+        pin: i32,
+        timeout: Duration,
+    ) -> Result<Vec<OneWireAddress>> {
+        self.send(&Command::OneWireSearchAlarms { pin: pin as u8 })?;
+        self.wait_for_onewire_search(pin as u8, true, timeout)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn serial_close(&mut self, port: SerialPortId) -> Result<()> {
+        self.require(Feature::Serial)?;
+        self.send(&Command::SerialClose { port })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn serial_config(
+        &mut self,
+        port: SerialPortId,
+        baud: i32,
+        pins: Option<(i32, i32)>,
+    ) -> Result<()> {
+        self.require(Feature::Serial)?;
+        self.send(&Command::SerialConfig {
+            port,
+            baud: baud as u32,
+            pins: pins.map(|(rx, tx)| (rx as u8, tx as u8)),
+        })?;
+        // The firmware switches the pins of software serial ports to serial mode.
+        if let Some((rx, tx)) = pins {
+            self.update_mode(rx as u8, PinMode::Serial);
+            self.update_mode(tx as u8, PinMode::Serial);
         }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn serial_flush(&mut self, port: SerialPortId) -> Result<()> {
+        self.require(Feature::Serial)?;
+        self.send(&Command::SerialFlush { port })
+    }
 
-        self.write(&[
-            DIGITAL_MESSAGE | port as u8,
-            value as u8 & SYSEX_REALTIME,
-            (value >> 7) as u8 & SYSEX_REALTIME,
-        ])
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn serial_listen(&mut self, port: SerialPortId) -> Result<()> {
+        self.require(Feature::Serial)?;
+        self.send(&Command::SerialListen { port })
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn set_pin_mode(&mut self, pin: i32, mode: u8) -> Result<()> {
-        self.pins[pin as usize].modes = vec![mode];
-        self.write(&[SET_PIN_MODE, pin as u8, mode])
+    fn serial_read_continuously(
+        &mut self,
+        port: SerialPortId,
+        max_bytes: Option<i32>,
+    ) -> Result<()> {
+        self.require(Feature::Serial)?;
+        self.send(&Command::SerialRead {
+            port,
+            max_bytes: max_bytes.map(|max_bytes| max_bytes as u16),
+        })
     }
 
     #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
-    fn read_and_decode(&mut self) -> Result<Message> {
-        let mut buf = vec![0; 3];
-        self.connection
-            .read_exact(&mut buf)
-            .with_context(|_| StdIoSnafu)?;
-        match buf[0] {
-            REPORT_VERSION => {
-                self.protocol_version = format!("{:o}.{:o}", buf[1], buf[2]);
-                Ok(Message::ProtocolVersion)
+    fn serial_stop_reading(&mut self, port: SerialPortId) -> Result<()> {
+        self.require(Feature::Serial)?;
+        self.send(&Command::SerialStopReading { port })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn serial_write(&mut self, port: SerialPortId, data: &[u8]) -> Result<()> {
+        self.require(Feature::Serial)?;
+        for chunk in data.chunks(SERIAL_WRITE_MAX_BYTES) {
+            self.send(&Command::SerialWrite {
+                port,
+                data: chunk.to_vec(),
+            })?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn servo_config(&mut self, pin: i32, min_pulse: i32, max_pulse: i32) -> Result<()> {
+        ensure!(
+            self.capabilities.supports(pin as u8, PinMode::Servo),
+            UnsupportedPinModeSnafu {
+                pin: pin as u8,
+                mode: PinMode::Servo
             }
-            ANALOG_MESSAGE..=ANALOG_MESSAGE_BOUND => {
-                if buf.len() < 3 {
-                    return Err(Error::MessageTooShort);
-                }
-                let pin = ((buf[0] as i32) & 0x0F) + 14;
-                let value = (buf[1] as i32) | ((buf[2] as i32) << 7);
-                if self.pins.len() as i32 > pin {
-                    self.pins[pin as usize].value = value;
-                }
-                Ok(Message::Analog)
+        );
+        // Unlike in `servo_write_micros`, pulses below `SERVO_MIN_PULSE` are valid calibrations.
+        ensure!(
+            0 <= min_pulse && min_pulse < max_pulse && max_pulse <= 0x3FFF,
+            InvalidServoRangeSnafu {
+                min_pulse,
+                max_pulse
             }
-            DIGITAL_MESSAGE..=DIGITAL_MESSAGE_BOUND => {
-                if buf.len() < 3 {
-                    return Err(Error::MessageTooShort);
-                }
-                let port = (buf[0] as i32) & 0x0F;
-                let value = (buf[1] as i32) | ((buf[2] as i32) << 7);
+        );
+        self.pin_mut(pin)?;
+        self.send(&Command::ServoConfig {
+            pin: pin as u8,
+            min_pulse: min_pulse as u16,
+            max_pulse: max_pulse as u16,
+        })?;
+        // The firmware attaches the servo and switches the pin to servo mode.
+        self.update_mode(pin as u8, PinMode::Servo);
+        Ok(())
+    }
 
-                for i in 0..8 {
-                    let pin = (8 * port) + i;
-                    let mode: u8 = self.pins[pin as usize].mode;
-                    if self.pins.len() as i32 > pin && mode == PIN_MODE_INPUT {
-                        self.pins[pin as usize].value = (value >> (i & 0x07)) & 0x01;
-                    }
-                }
-                Ok(Message::Digital)
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn servo_write_degrees(&mut self, pin: i32, degrees: i32) -> Result<()> {
+        ensure!(
+            (0..=180).contains(&degrees),
+            InvalidServoAngleSnafu { degrees }
+        );
+        // Values below the minimum pulse width are angles, which the firmware maps onto the pulse
+        // widths configured with `servo_config`.
+        self.analog_write(pin, degrees)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn servo_write_micros(&mut self, pin: i32, micros: i32) -> Result<()> {
+        ensure!(
+            (SERVO_MIN_PULSE as i32..=0x3FFF).contains(&micros),
+            InvalidServoPulseSnafu { micros }
+        );
+        self.analog_write(pin, micros)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn set_digital_pin_value(&mut self, pin: i32, level: i32) -> Result<()> {
+        self.require(Feature::SetDigitalPinValue)?;
+        self.pin_mut(pin)?.value = level;
+        self.send(&Command::SetDigitalPinValue {
+            pin: pin as u8,
+            value: level != 0,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn set_pin_mode(&mut self, pin: i32, mode: PinMode) -> Result<()> {
+        if mode == PinMode::Pullup {
+            self.require(Feature::Pullup)?;
+        }
+        ensure!(
+            mode == PinMode::Ignore || self.capabilities.supports(pin as u8, mode),
+            UnsupportedPinModeSnafu {
+                pin: pin as u8,
+                mode
             }
-            START_SYSEX => {
-                loop {
-                    // Read until END_SYSEX.
-                    let mut byte = [0];
-                    self.connection
-                        .read_exact(&mut byte)
-                        .with_context(|_| StdIoSnafu)?;
-                    buf.push(byte[0]);
-                    if byte[0] == END_SYSEX {
-                        break;
-                    }
-                }
-                match buf[1] {
-                    END_SYSEX => Ok(Message::EmptyResponse),
-                    ANALOG_MAPPING_RESPONSE => {
-                        let mut i = 2;
-                        // Also break before pins indexing is out of bounds.
-                        let upper = (buf.len() - 1).min(self.pins.len() + 2);
-                        while i < upper {
-                            if buf[i] != 127u8 {
-                                let pin = &mut self.pins[i - 2];
-                                pin.mode = PIN_MODE_ANALOG;
-                                pin.modes = vec![PIN_MODE_ANALOG];
-                                pin.resolution = DEFAULT_ANALOG_RESOLUTION;
-                            }
-                            i += 1;
-                        }
-                        Ok(Message::AnalogMappingResponse)
-                    }
-                    CAPABILITY_RESPONSE => {
-                        let mut i = 2;
-                        self.pins = vec![];
-                        self.pins.push(Pin::default()); // 0 is unused.
-                        let mut modes = vec![];
-                        let mut resolution = None;
-                        while i < buf.len() - 1 {
-                            // Completed a pin, push and continue.
-                            if buf[i] == 127u8 {
-                                self.pins.push(Pin {
-                                    mode: *modes.first().expect("pin mode"),
-                                    modes: modes.drain(..).collect(),
-                                    resolution: resolution.take().expect("pin resolution"),
-                                    value: 0,
-                                });
-
-                                i += 1;
-                            } else {
-                                modes.push(buf[i]);
-                                if resolution.is_none() {
-                                    // Only keep the first.
-                                    resolution.replace(buf[i + 1]);
-                                }
-                                i += 2;
-                            }
-                        }
-                        Ok(Message::CapabilityResponse)
-                    }
-                    REPORT_FIRMWARE => {
-                        let major = buf.get(2).with_context(|| MessageTooShortSnafu)?;
-                        let minor = buf.get(3).with_context(|| MessageTooShortSnafu)?;
-                        self.firmware_version = format!("{:o}.{:o}", major, minor);
-                        if 4 < buf.len() - 1 {
-                            self.firmware_name = std::str::from_utf8(&buf[4..buf.len() - 1])
-                                .with_context(|_| Utf8Snafu)?
-                                .to_string();
-                        }
-                        Ok(Message::ReportFirmware)
-                    }
-                    I2C_REPLY => {
-                        let len = buf.len();
-                        if len < 8 {
-                            return Err(Error::MessageTooShort);
-                        }
-                        let mut reply = I2CReply {
-                            address: (buf[2] as i32) | ((buf[3] as i32) << 7),
-                            register: (buf[4] as i32) | ((buf[5] as i32) << 7),
-                            data: vec![buf[6] | buf[7] << 7],
-                        };
-                        let mut i = 8;
-
-                        while i < len - 1 {
-                            if buf[i] == 0xF7 {
-                                break;
-                            }
-                            if i + 2 > len {
-                                break;
-                            }
-                            reply.data.push(buf[i] | buf[i + 1] << 7);
-                            i += 2;
-                        }
-                        self.i2c_data.push(reply);
-                        Ok(Message::I2CReply)
-                    }
-                    PIN_STATE_RESPONSE => {
-                        let pin = buf[2];
-                        if buf[3] == END_SYSEX {
-                            return Ok(Message::PinStateResponse);
-                        }
-                        let pin = &mut self.pins[pin as usize];
-                        pin.modes = vec![buf[3]];
-                        // TODO: Extended values.
-                        pin.value = buf[4] as i32;
+        );
+        self.pin_mut(pin)?;
+        self.update_mode(pin as u8, mode);
+        self.send(&Command::SetPinMode {
+            pin: pin as u8,
+            mode,
+        })
+    }
 
-                        Ok(Message::PinStateResponse)
-                    }
-                    _ => Err(Error::UnknownSysEx { code: buf[1] }),
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn set_sampling_interval(&mut self, interval: Duration) -> Result<()> {
+        let millis = interval.as_millis();
+        ensure!(
+            (1..=0x3FFF).contains(&millis),
+            InvalidSamplingIntervalSnafu { interval }
+        );
+        self.send(&Command::SamplingInterval {
+            interval: millis as u16,
+        })?;
+        // The board only has millisecond resolution, so remember the interval it actually uses.
+        self.sampling_interval = Duration::from_millis(millis as u64);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn shift_in(
+        &mut self,
+        data_pin: i32,
+        clock_pin: i32,
+        bit_order: BitOrder,
+        size: i32,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        ensure!((1..=127).contains(&size), InvalidShiftSizeSnafu { size });
+        self.send(&Command::ShiftIn {
+            data_pin: data_pin as u8,
+            clock_pin: clock_pin as u8,
+            bit_order,
+            size: size as u8,
+        })?;
+        let message = self.wait_for(timeout, |message| {
+            matches!(message, Message::ShiftInReply { data_pin: pin, .. } if *pin as i32 == data_pin)
+        })?;
+        let Message::ShiftInReply { data, .. } = message else {
+            unreachable!("waited for a shift in reply");
+        };
+        Ok(data)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn shift_out(
+        &mut self,
+        data_pin: i32,
+        clock_pin: i32,
+        bit_order: BitOrder,
+        data: &[u8],
+    ) -> Result<()> {
+        for chunk in data.chunks(SHIFT_OUT_MAX_BYTES) {
+            self.send(&Command::ShiftOut {
+                data_pin: data_pin as u8,
+                clock_pin: clock_pin as u8,
+                bit_order,
+                data: chunk.to_vec(),
+            })?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn spi_begin(&mut self, channel: i32) -> Result<()> {
+        self.send(&Command::SpiBegin {
+            channel: channel as u8,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn spi_device_config(&mut self, device: SpiDevice, config: SpiDeviceConfig) -> Result<()> {
+        self.send(&Command::SpiDeviceConfig { device, config })?;
+        if let Some(cs_pin) = config.cs_pin {
+            // The firmware drives the chip select pin as an output.
+            self.update_mode(cs_pin, PinMode::Output);
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn spi_end(&mut self, channel: i32) -> Result<()> {
+        self.send(&Command::SpiEnd {
+            channel: channel as u8,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn spi_read(
+        &mut self,
+        device: SpiDevice,
+        size: i32,
+        deselect: bool,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        ensure!(size >= 1, InvalidSpiReadSizeSnafu { size });
+        let size = size as usize;
+        let mut data = Vec::with_capacity(size);
+        // Only deselect the device after the last chunk, so the read stays one transaction.
+        let chunks = size.div_ceil(SPI_MAX_WORDS);
+        for chunk in 0..chunks {
+            let request_id = self.next_spi_request_id();
+            self.send(&Command::SpiRead {
+                device,
+                request_id,
+                deselect: deselect && chunk + 1 == chunks,
+                size: (size - chunk * SPI_MAX_WORDS).min(SPI_MAX_WORDS) as u8,
+            })?;
+            data.extend(self.wait_for_spi_reply(request_id, timeout)?);
+        }
+        Ok(data)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn spi_transfer(
+        &mut self,
+        device: SpiDevice,
+        data: &[u8],
+        deselect: bool,
+        timeout: Duration,
+    ) -> Result<Vec<u8>> {
+        let mut reply = Vec::with_capacity(data.len());
+        // Only deselect the device after the last chunk, so the transfer stays one transaction.
+        let chunks = data.len().div_ceil(SPI_MAX_WORDS);
+        for (i, chunk) in data.chunks(SPI_MAX_WORDS).enumerate() {
+            let request_id = self.next_spi_request_id();
+            self.send(&Command::SpiTransfer {
+                device,
+                request_id,
+                deselect: deselect && i + 1 == chunks,
+                data: chunk.to_vec(),
+            })?;
+            reply.extend(self.wait_for_spi_reply(request_id, timeout)?);
+        }
+        Ok(reply)
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn spi_write(&mut self, device: SpiDevice, data: &[u8], deselect: bool) -> Result<()> {
+        let chunks = data.len().div_ceil(SPI_MAX_WORDS);
+        for (i, chunk) in data.chunks(SPI_MAX_WORDS).enumerate() {
+            let request_id = self.next_spi_request_id();
+            self.send(&Command::SpiWrite {
+                device,
+                request_id,
+                deselect: deselect && i + 1 == chunks,
+                data: chunk.to_vec(),
+            })?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn stepper_config(
+        &mut self,
+        device: i32,
+        steps_per_revolution: i32,
+        interface: StepperInterface,
+    ) -> Result<()> {
+        for pin in interface.pins() {
+            ensure!(
+                self.capabilities.supports(pin, PinMode::Stepper),
+                UnsupportedPinModeSnafu {
+                    pin,
+                    mode: PinMode::Stepper
                 }
+            );
+        }
+        self.send(&Command::StepperConfig {
+            device: device as u8,
+            steps_per_revolution: steps_per_revolution as u16,
+            interface,
+        })?;
+        // The firmware switches the pins of the stepper to stepper mode.
+        for pin in interface.pins() {
+            self.update_mode(pin, PinMode::Stepper);
+        }
+        self.steppers.insert(device as u8, interface);
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn stepper_step(
+        &mut self,
+        device: i32,
+        steps: i32,
+        speed: i32,
+        ramp: Option<StepperRamp>,
+    ) -> Result<()> {
+        ensure!(
+            steps.unsigned_abs() <= 0x1F_FFFF,
+            InvalidStepperStepsSnafu { steps }
+        );
+        self.send(&Command::StepperStep {
+            device: device as u8,
+            direction: match steps {
+                0.. => StepperDirection::Clockwise,
+                _ => StepperDirection::CounterClockwise,
+            },
+            steps: steps.unsigned_abs(),
+            speed: speed as u16,
+            ramp,
+        })
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn system_reset(&mut self, reinitialize: bool) -> Result<()> {
+        self.send(&Command::SystemReset)?;
+        // Bytes of a message cut short by the reset never complete.
+        self.decoder = Decoder::new();
+        self.pending.clear();
+
+        for pin in 0..self.pins.len() {
+            self.reset_mode(pin as u8);
+            self.pins[pin].value = 0;
+        }
+        self.i2c_data.clear();
+        self.encoder_positions.clear();
+        self.steppers.clear();
+        self.accel_steppers.clear();
+        self.accel_stepper_positions.clear();
+        self.multi_steppers.clear();
+        self.onewire_data.clear();
+        self.serial_data.clear();
+        self.spi_data.clear();
+        self.spi_request_id = 0;
+        self.sampling_interval = Duration::from_millis(DEFAULT_SAMPLING_INTERVAL as u64);
+
+        if reinitialize {
+            self.initialize_board()?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), err, ret, level = "DEBUG")]
+    fn read_and_decode(&mut self) -> Result<Message> {
+        match self.pending.pop_front() {
+            Some(message) => Ok(message),
+            None => self.read_message(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+
+    /// In-memory connection that serves `rx` and records everything written to `tx`.
+    #[derive(Debug, Default)]
+    struct MockConnection {
+        rx: VecDeque<u8>,
+        tx: Vec<u8>,
+    }
+    impl Read for MockConnection {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.rx.is_empty() {
+                return Err(std::io::ErrorKind::TimedOut.into());
+            }
+            let n = buf.len().min(self.rx.len());
+            for b in buf.iter_mut().take(n) {
+                *b = self.rx.pop_front().unwrap();
+            }
+            Ok(n)
+        }
+    }
+    impl Write for MockConnection {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.tx.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// An Uno-like board with `pins` pins that will receive `rx`: every pin is digital and pins
+    /// from 14 on are analog too.
+    fn board(pins: usize, rx: &[u8]) -> Board<MockConnection> {
+        let mut board = Board::with_connection(Box::new(MockConnection {
+            rx: rx.iter().copied().collect(),
+            tx: vec![],
+        }));
+        let digital =
+            [PinMode::Input, PinMode::Output, PinMode::Pullup].map(|mode| ModeCapability {
+                mode,
+                resolution: 1,
+            });
+        let analog = ModeCapability {
+            mode: PinMode::Analog,
+            resolution: 10,
+        };
+        let capabilities = Capabilities {
+            pins: (0..pins)
+                .map(|pin| match pin {
+                    0..=13 => digital.to_vec(),
+                    _ => [&digital[..], &[analog]].concat(),
+                })
+                .collect(),
+        };
+        board.update(&Message::CapabilityResponse(capabilities));
+        board
+    }
+
+    #[test]
+    fn analog_reply_updates_mapped_pin() {
+        let mut board = board(20, &[0xE2, 0x7F, 0x03]);
+        // Channel 2 is not on pin 16 (14 + 2) on this board.
+        board.analog_mapping = vec![None; 20];
+        board.analog_mapping[5] = Some(2);
+        assert_eq!(
+            board.read_and_decode().unwrap(),
+            Message::Analog {
+                channel: 2,
+                value: 511
+            }
+        );
+        assert_eq!(board.pins[5].value, 511);
+        assert_eq!(board.pins[16].value, 0);
+    }
+
+    #[test]
+    fn digital_reply_updates_input_pins() {
+        let mut board = board(16, &[0x91, 0x05, 0x00]);
+        for pin in &mut board.pins {
+            pin.mode = PinMode::Input;
+        }
+        board.pins[10].mode = PinMode::Output;
+        assert_eq!(
+            board.read_and_decode().unwrap(),
+            Message::Digital {
+                port: 1,
+                mask: 0x05
+            }
+        );
+        assert_eq!(board.pins[8].value, 1);
+        assert_eq!(board.pins[9].value, 0);
+        assert_eq!(board.pins[10].value, 0);
+        assert_eq!(board.pins[0].value, 0);
+    }
+
+    #[test]
+    fn capability_reply_replaces_pins() {
+        // Pin 0 is digital, pin 1 analog and PWM and pin 2 supports no modes.
+        let mut board = board(
+            0,
+            &[
+                0xF0, 0x6C, 0x00, 0x01, 0x01, 0x01, 0x7F, 0x02, 0x0A, 0x03, 0x08, 0x7F, 0x7F, 0xF7,
+            ],
+        );
+        assert!(matches!(
+            board.read_and_decode().unwrap(),
+            Message::CapabilityResponse(_)
+        ));
+        // Pins are numbered like the firmware, without a placeholder at index 0.
+        assert_eq!(board.pins.len(), 3);
+        assert_eq!(board.pins[0].mode, PinMode::Output);
+        assert_eq!(board.pins[0].modes, vec![PinMode::Input, PinMode::Output]);
+        assert_eq!(board.pins[1].mode, PinMode::Analog);
+        assert_eq!(board.pins[1].resolution, 10);
+        assert_eq!(board.pins[2].mode, PinMode::Ignore);
+        assert!(board.pins[2].modes.is_empty());
+
+        // The resolution follows the current mode.
+        board.set_pin_mode(1, PinMode::Pwm).unwrap();
+        assert_eq!(board.pins[1].resolution, 8);
+        assert!(matches!(
+            board.set_pin_mode(0, PinMode::Pwm),
+            Err(Error::UnsupportedPinMode { pin: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn analog_mapping_reply_marks_analog_pins() {
+        let mut board = board(3, &[0xF0, 0x6A, 0x7F, 0x7F, 0x00, 0xF7]);
+        for pin in &mut board.pins {
+            pin.mode = PinMode::Output;
+            pin.modes = vec![PinMode::Input, PinMode::Output];
+        }
+        assert_eq!(
+            board.read_and_decode().unwrap(),
+            Message::AnalogMappingResponse(vec![None, None, Some(0)])
+        );
+        assert_eq!(board.pins[0].mode, PinMode::Output);
+        assert_eq!(board.pins[2].mode, PinMode::Analog);
+        assert_eq!(board.analog_channel(2), Some(0));
+        assert_eq!(board.analog_channel(0), None);
+        assert_eq!(board.analog_channel(-1), None);
+        assert_eq!(board.pin_for_channel(0), Some(2));
+        assert_eq!(board.pin_for_channel(1), None);
+
+        board.report_analog(2, 1).unwrap();
+        assert_eq!(board.connection.tx, [0xC0, 0x01]);
+        assert!(matches!(
+            board.report_analog(0, 1),
+            Err(Error::NotAnalog { pin: 0 })
+        ));
+    }
+
+    #[test]
+    fn refuse_features_of_newer_protocol_versions() {
+        let mut old = board(20, &[0xF9, 0x02, 0x03]);
+        old.read_and_decode().unwrap();
+        assert!(matches!(
+            old.set_pin_mode(2, PinMode::Pullup),
+            Err(Error::UnsupportedFeature {
+                feature: Feature::Pullup,
+                ..
+            })
+        ));
+        assert!(old.connection.tx.is_empty());
+
+        let mut board = board(20, &[0xF9, 0x02, 0x05]);
+        board.read_and_decode().unwrap();
+        board.set_pin_mode(2, PinMode::Pullup).unwrap();
+        assert_eq!(board.connection.tx, [0xF4, 0x02, 0x0B]);
+    }
+
+    #[test]
+    fn pin_state_reply_updates_mode_and_value() {
+        let mut board = board(20, &[0xF0, 0x6E, 0x0D, 0x01, 0x01, 0xF7]);
+        board.query_pin_state(13).unwrap();
+        assert_eq!(board.connection.tx, [0xF0, 0x6D, 0x0D, 0xF7]);
+        board.read_and_decode().unwrap();
+        assert_eq!(board.pins[13].mode, PinMode::Output);
+        assert_eq!(
+            board.pins[13].modes,
+            vec![PinMode::Input, PinMode::Output, PinMode::Pullup]
+        );
+        assert_eq!(board.pins[13].value, 1);
+    }
+
+    #[test]
+    fn analog_write_falls_back_to_extended_analog() {
+        let mut board = board(20, &[]);
+        board.analog_write(3, 200).unwrap();
+        board.analog_write(16, 200).unwrap();
+        board.analog_write(3, 0x4000).unwrap();
+        assert_eq!(
+            board.connection.tx,
+            [
+                0xE3, 0x48, 0x01, // analog message
+                0xF0, 0x6F, 0x10, 0x48, 0x01, 0xF7, // pin above 15
+                0xF0, 0x6F, 0x03, 0x00, 0x00, 0x01, 0xF7, // value over 14 bits
+            ]
+        );
+        assert_eq!(board.pins[16].value, 200);
+        assert!(matches!(
+            board.analog_write(3, -1),
+            Err(Error::InvalidAnalogLevel { level: -1 })
+        ));
+    }
+
+    #[test]
+    fn digital_write_port_keeps_other_outputs() {
+        let mut board = board(16, &[0xF9, 0x02, 0x03]);
+        board.read_and_decode().unwrap();
+        for pin in &mut board.pins {
+            pin.mode = PinMode::Output;
+        }
+        board.pins[9].value = 1;
+        // High input pins (pull-ups) and pins in other modes stay out of the port message.
+        board.pins[10].mode = PinMode::Input;
+        board.pins[10].value = 1;
+        board.pins[11].mode = PinMode::Analog;
+        board.pins[11].value = 1;
+        board.digital_write(12, 1).unwrap();
+        assert_eq!(board.connection.tx, [0x91, 0x12, 0x00]);
+        assert_eq!(board.pins[12].value, 1);
+
+        board.connection.tx.clear();
+        board.digital_write(9, 0).unwrap();
+        assert_eq!(board.connection.tx, [0x91, 0x10, 0x00]);
+    }
+
+    #[test]
+    fn digital_write_single_pin() {
+        let mut board = board(16, &[0xF9, 0x02, 0x05]);
+        board.read_and_decode().unwrap();
+        board.digital_write(13, 1).unwrap();
+        assert_eq!(board.connection.tx, [0xF5, 0x0D, 0x01]);
+        assert_eq!(board.pins[13].value, 1);
+    }
+
+    #[test]
+    fn sampling_interval_in_whole_milliseconds() {
+        let mut board = board(0, &[]);
+        board
+            .set_sampling_interval(Duration::from_micros(19_900))
+            .unwrap();
+        assert_eq!(board.connection.tx, [0xF0, 0x7A, 0x13, 0x00, 0xF7]);
+        assert_eq!(board.sampling_interval, Duration::from_millis(19));
+        assert!(matches!(
+            board.set_sampling_interval(Duration::from_micros(900)),
+            Err(Error::InvalidSamplingInterval { .. })
+        ));
+    }
+
+    #[test]
+    fn system_reset_resets_local_state() {
+        let mut board = board(16, &[0xF0, 0x79]);
+        board.pins[0].mode = PinMode::Input;
+        board.pins[0].value = 1;
+        board.pins[14].mode = PinMode::Output;
+        board.pins[14].value = 1;
+        board.i2c_data.push(I2CReply::default());
+        board.sampling_interval = Duration::from_millis(100);
+        board.spi_request_id = 42;
+        // Half a message that the reset cuts short.
+        assert!(board.read_and_decode().is_err());
+        assert_eq!(board.decoder.buffered(), 2);
+        board.pending.push_back(Message::Analog {
+            channel: 0,
+            value: 1,
+        });
+
+        board.system_reset(false).unwrap();
+        assert_eq!(board.connection.tx, [0xFF]);
+        assert_eq!(board.pins[0].mode, PinMode::Output);
+        assert_eq!(board.pins[14].mode, PinMode::Analog);
+        assert!(board.pins.iter().all(|pin| pin.value == 0));
+        assert!(board.i2c_data.is_empty());
+        assert_eq!(
+            board.sampling_interval,
+            Duration::from_millis(DEFAULT_SAMPLING_INTERVAL as u64)
+        );
+        assert_eq!(board.decoder.buffered(), 0);
+        assert!(board.pending.is_empty());
+        assert_eq!(board.spi_request_id, 0);
+    }
+
+    #[test]
+    fn i2c_read_register_keeps_other_messages() {
+        let mut board = board(16, &[]);
+        board.connection.rx.extend([
+            0xE0, 0x01, 0x00, // Analog channel 0.
+            0xF0, 0x01, 0xF7, // Unknown sysex, skipped.
+            0xF0, 0x77, 0x09, 0x00, 0x02, 0x00, 0x10, 0x00, 0xF7,
+        ]);
+        assert_eq!(
+            board
+                .i2c_read_register(0x09, 0x02, 1, Duration::from_secs(1))
+                .unwrap(),
+            [0x10]
+        );
+        assert!(board.i2c_data.is_empty());
+        assert_eq!(board.pending.len(), 1);
+        assert_eq!(
+            board.read_and_decode().unwrap(),
+            Message::Analog {
+                channel: 0,
+                value: 1
+            }
+        );
+        assert!(board.pending.is_empty());
+    }
+
+    #[test]
+    fn i2c_read_register_times_out() {
+        let mut board = board(16, &[0xE0, 0x01, 0x00]);
+        let timeout = Duration::from_millis(10);
+        assert!(matches!(
+            board.i2c_read_register(0x09, 0x02, 1, timeout),
+            Err(Error::Timeout { timeout: t }) if t == timeout
+        ));
+        assert_eq!(board.pending.len(), 1);
+    }
+
+    #[test]
+    fn serial_handle_round_trip() {
+        let port = SerialPortId::Hardware(1);
+        let mut board = board(16, &[]);
+        board.connection.rx.extend([
+            0xE0, 0x01, 0x00, // Analog channel 0.
+            0xF0, 0x60, 0x41, 0x6F, 0x00, 0x6B, 0x00, 0xF7,
+        ]);
+        let mut serial = board.serial(port);
+        serial.write_all(b"hi").unwrap();
+        let mut buf = [0; 8];
+        let n = serial.read(&mut buf).unwrap();
+        assert_eq!(&buf[..n], b"ok");
+        assert_eq!(
+            board.connection.tx,
+            [0xF0, 0x60, 0x21, 0x68, 0x00, 0x69, 0x00, 0xF7]
+        );
+        assert_eq!(
+            board.pending,
+            [Message::Analog {
+                channel: 0,
+                value: 1
+            }]
+        );
+    }
+
+    #[test]
+    fn encoder_reply_updates_positions() {
+        let mut board = board(16, &[0xF0, 0x61, 0x41, 0x2C, 0x02, 0x00, 0x00, 0xF7]);
+        board.encoder_positions.insert(1, 12);
+        board.read_and_decode().unwrap();
+        assert_eq!(board.encoder_positions[&1], -300);
+    }
+
+    #[test]
+    fn accel_stepper_config_sets_pin_modes() {
+        let mut board = board(
+            16,
+            &[0xF0, 0x62, 0x0A, 0x00, 0x68, 0x07, 0x00, 0x00, 0x08, 0xF7],
+        );
+        for pin in [2, 3] {
+            board.capabilities.pins[pin].push(ModeCapability {
+                mode: PinMode::Stepper,
+                resolution: 21,
+            });
+        }
+        let config = AccelStepperConfig::new(AccelStepperInterface::Driver {
+            step_pin: 2,
+            direction_pin: 3,
+        })
+        .enable_pin(4);
+        board.pins[4].mode = PinMode::Input;
+        board.accel_stepper_config(0, config).unwrap();
+        assert_eq!(board.pins[2].mode, PinMode::Stepper);
+        assert_eq!(board.pins[3].mode, PinMode::Stepper);
+        assert_eq!(board.pins[4].mode, PinMode::Output);
+        assert_eq!(board.accel_stepper_positions[&0], 0);
+
+        board.read_and_decode().unwrap();
+        assert_eq!(board.accel_stepper_positions[&0], -1000);
+    }
+
+    #[test]
+    fn scheduler_create_task_from_sizes_task() {
+        let mut board = board(16, &[]);
+        let task = [
+            Command::SetDigitalPinValue {
+                pin: 13,
+                value: true,
+            },
+            Command::SchedulerDelayTask { delay: 400 },
+        ];
+        board.scheduler_create_task_from(0, &task).unwrap();
+        let tx = &board.connection.tx;
+        assert_eq!(tx[..7], [0xF0, 0x7B, 0x00, 0x00, 0x0C, 0x00, 0xF7]);
+        assert_eq!(
+            Command::decode(&tx[7..]).unwrap(),
+            Command::SchedulerAddToTask {
+                task: 0,
+                data: [task[0].encode().unwrap(), task[1].encode().unwrap()].concat()
             }
-            _ => Err(Error::BadByte { byte: buf[0] }),
+        );
+        for length in [0, 0x4000] {
+            assert!(matches!(
+                board.scheduler_create_task(0, length),
+                Err(Error::InvalidSchedulerTaskLength { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn shift_in_waits_for_reply() {
+        let mut board = board(16, &[0xF0, 0x75, 0x03, 0x04, 0x25, 0x01, 0xF7]);
+        let timeout = Duration::from_secs(1);
+        assert_eq!(
+            board
+                .shift_in(4, 5, BitOrder::MsbFirst, 1, timeout)
+                .unwrap(),
+            [0xA5]
+        );
+        assert_eq!(
+            board.connection.tx,
+            [0xF0, 0x75, 0x02, 0x04, 0x05, 0x01, 0x01, 0xF7]
+        );
+        for size in [0, 128] {
+            assert!(matches!(
+                board.shift_in(4, 5, BitOrder::MsbFirst, size, timeout),
+                Err(Error::InvalidShiftSize { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn spi_transfer_matches_request_ids() {
+        let device = SpiDevice::new(0, 1);
+        let timeout = Duration::from_secs(1);
+        let mut board = board(16, &[]);
+        board.connection.rx.extend([
+            0xF0, 0x68, 0x05, 0x08, 0x05, 0x01, 0x11, 0x00, 0xF7, // Request 5.
+            0xF0, 0x68, 0x05, 0x08, 0x7F, 0x01, 0x5A, 0x00, 0xF7, // Request 127.
+            0xF0, 0x68, 0x05, 0x08, 0x00, 0x01, 0x33, 0x00, 0xF7, // Request 0.
+        ]);
+        board.spi_request_id = 127;
+        assert_eq!(
+            board.spi_transfer(device, &[0xA5], true, timeout).unwrap(),
+            [0x5A]
+        );
+        assert_eq!(
+            board.connection.tx,
+            [0xF0, 0x68, 0x02, 0x08, 0x7F, 0x01, 0x01, 0x25, 0x01, 0xF7]
+        );
+        // The ID wraps around, and the reply to another request is kept.
+        assert_eq!(board.spi_request_id, 0);
+        assert_eq!(board.spi_data[&5], [0x11]);
+        assert_eq!(board.pending.len(), 1);
+
+        board.connection.tx.clear();
+        assert_eq!(
+            board.spi_transfer(device, &[0xA5], true, timeout).unwrap(),
+            [0x33]
+        );
+        assert_eq!(board.connection.tx[4], 0x00);
+        assert!(!board.spi_data.contains_key(&0));
+    }
+
+    #[test]
+    fn spi_read_rejects_empty_reads() {
+        let mut board = board(16, &[]);
+        for size in [0, -1] {
+            assert!(matches!(
+                board.spi_read(SpiDevice::new(0, 1), size, true, Duration::from_secs(1)),
+                Err(Error::InvalidSpiReadSize { .. })
+            ));
         }
+        assert!(board.connection.tx.is_empty());
+    }
+
+    #[test]
+    fn servo_config_attaches_servo() {
+        let mut board = board(16, &[]);
+        board.capabilities.pins[9].push(ModeCapability {
+            mode: PinMode::Servo,
+            resolution: 14,
+        });
+        board.servo_config(9, 500, 2500).unwrap();
+        assert_eq!(board.pins[9].mode, PinMode::Servo);
+        assert_eq!(board.pins[9].resolution, 14);
+        board.servo_write_degrees(9, 90).unwrap();
+        assert_eq!(
+            board.connection.tx,
+            [
+                0xF0, 0x70, 0x09, 0x74, 0x03, 0x44, 0x13, 0xF7, // servo config
+                0xE9, 0x5A, 0x00, // 90 degrees
+            ]
+        );
+
+        assert!(matches!(
+            board.servo_config(9, 2500, 500),
+            Err(Error::InvalidServoRange { .. })
+        ));
+        assert!(matches!(
+            board.servo_config(8, 500, 2500),
+            Err(Error::UnsupportedPinMode { pin: 8, .. })
+        ));
     }
 }
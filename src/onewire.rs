@@ -0,0 +1,132 @@
+//! OneWire bus addresses and requests.
+
+use crate::*;
+use std::fmt;
+
+/// 64-bit ROM address of a OneWire device: family code, 48-bit serial number and CRC.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OneWireAddress([u8; 8]);
+impl OneWireAddress {
+    /// Creates a new `OneWireAddress` from its `bytes` in bus order, if the CRC matches.
+    pub fn new(bytes: [u8; 8]) -> Result<Self> {
+        ensure!(
+            crc8(&bytes[..7]) == bytes[7],
+            InvalidOneWireAddressSnafu {
+                address: u64::from_le_bytes(bytes)
+            }
+        );
+        Ok(Self(bytes))
+    }
+    /// Family code, which identifies the type of device.
+    pub fn family(&self) -> u8 {
+        self.0[0]
+    }
+    /// 48-bit serial number.
+    pub fn serial(&self) -> u64 {
+        self.0[1..7]
+            .iter()
+            .rev()
+            .fold(0, |serial, &byte| serial << 8 | byte as u64)
+    }
+    /// Address bytes in bus order.
+    pub fn as_bytes(&self) -> &[u8; 8] {
+        &self.0
+    }
+}
+impl TryFrom<u64> for OneWireAddress {
+    type Error = Error;
+
+    /// Convert an address with the family code in the least significant byte.
+    fn try_from(value: u64) -> Result<Self> {
+        Self::new(value.to_le_bytes())
+    }
+}
+impl From<OneWireAddress> for u64 {
+    fn from(value: OneWireAddress) -> Self {
+        u64::from_le_bytes(value.0)
+    }
+}
+impl fmt::Display for OneWireAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, "-")?;
+            }
+            write!(f, "{byte:02X}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Dallas/Maxim CRC-8 of `data`, as used in OneWire addresses and scratchpads.
+pub fn crc8(data: &[u8]) -> u8 {
+    let mut crc = 0;
+    for &byte in data {
+        let mut byte = byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 0x01;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8C;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
+
+/// Steps to run on a OneWire bus in a single request.
+///
+/// Whatever order the steps are set in, the firmware runs them in the order reset, skip or select,
+/// write, read and delay.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct OneWireRequest {
+    /// Reset the bus first.
+    pub reset: bool,
+    /// Address all devices on the bus.
+    pub skip: bool,
+    /// Address a single device.
+    pub select: Option<OneWireAddress>,
+    /// Bytes to write.
+    pub write: Option<Vec<u8>>,
+    /// Number of bytes to read and the correlation ID to send back with them.
+    pub read: Option<(u16, u16)>,
+    /// Delay in milliseconds after the request.
+    pub delay: Option<u32>,
+}
+impl OneWireRequest {
+    /// Creates a new, empty `OneWireRequest`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Reset the bus first.
+    pub fn reset(mut self) -> Self {
+        self.reset = true;
+        self
+    }
+    /// Address all devices on the bus.
+    pub fn skip(mut self) -> Self {
+        self.skip = true;
+        self
+    }
+    /// Address the device at `address`.
+    pub fn select(mut self, address: OneWireAddress) -> Self {
+        self.select = Some(address);
+        self
+    }
+    /// Write `data` to the bus.
+    pub fn write(mut self, data: &[u8]) -> Self {
+        self.write = Some(data.to_vec());
+        self
+    }
+    /// Read `size` bytes, replied with `correlation_id`.
+    pub fn read(mut self, size: u16, correlation_id: u16) -> Self {
+        self.read = Some((size, correlation_id));
+        self
+    }
+    /// Wait `delay` milliseconds after the request.
+    pub fn delay(mut self, delay: u32) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+}
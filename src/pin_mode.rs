@@ -0,0 +1,101 @@
+//! Pin modes.
+
+use crate::*;
+use std::fmt;
+
+/// Mode of a pin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PinMode {
+    /// Digital input, same as INPUT defined in Arduino.
+    Input,
+    /// Digital output, same as OUTPUT defined in Arduino.
+    Output,
+    /// Analog input.
+    Analog,
+    /// PWM output.
+    Pwm,
+    /// Servo output.
+    Servo,
+    /// shiftIn/shiftOut.
+    Shift,
+    /// Included in I2C setup.
+    I2C,
+    /// 1-wire bus.
+    OneWire,
+    /// Stepper motor.
+    Stepper,
+    /// Rotary encoder.
+    Encoder,
+    /// Serial communication.
+    Serial,
+    /// Digital input with the internal pull-up resistor enabled.
+    Pullup,
+    /// Ignored by digitalWrite and capabilityResponse.
+    Ignore,
+    /// Any other mode, e.g. vendor specific ones.
+    Unknown(u8),
+}
+impl TryFrom<u8> for PinMode {
+    type Error = Error;
+
+    /// Convert a raw mode, which must be a 7-bit data byte.
+    fn try_from(value: u8) -> Result<Self> {
+        Ok(match value {
+            PIN_MODE_INPUT => PinMode::Input,
+            PIN_MODE_OUTPUT => PinMode::Output,
+            PIN_MODE_ANALOG => PinMode::Analog,
+            PIN_MODE_PWM => PinMode::Pwm,
+            PIN_MODE_SERVO => PinMode::Servo,
+            PIN_MODE_SHIFT => PinMode::Shift,
+            PIN_MODE_I2C => PinMode::I2C,
+            PIN_MODE_ONEWIRE => PinMode::OneWire,
+            PIN_MODE_STEPPER => PinMode::Stepper,
+            PIN_MODE_ENCODER => PinMode::Encoder,
+            PIN_MODE_SERIAL => PinMode::Serial,
+            PIN_MODE_PULLUP => PinMode::Pullup,
+            PIN_MODE_IGNORE => PinMode::Ignore,
+            0x80..=0xFF => return Err(Error::BadByte { byte: value }),
+            _ => PinMode::Unknown(value),
+        })
+    }
+}
+impl From<PinMode> for u8 {
+    fn from(value: PinMode) -> Self {
+        match value {
+            PinMode::Input => PIN_MODE_INPUT,
+            PinMode::Output => PIN_MODE_OUTPUT,
+            PinMode::Analog => PIN_MODE_ANALOG,
+            PinMode::Pwm => PIN_MODE_PWM,
+            PinMode::Servo => PIN_MODE_SERVO,
+            PinMode::Shift => PIN_MODE_SHIFT,
+            PinMode::I2C => PIN_MODE_I2C,
+            PinMode::OneWire => PIN_MODE_ONEWIRE,
+            PinMode::Stepper => PIN_MODE_STEPPER,
+            PinMode::Encoder => PIN_MODE_ENCODER,
+            PinMode::Serial => PIN_MODE_SERIAL,
+            PinMode::Pullup => PIN_MODE_PULLUP,
+            PinMode::Ignore => PIN_MODE_IGNORE,
+            PinMode::Unknown(mode) => mode,
+        }
+    }
+}
+impl fmt::Display for PinMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PinMode::Input => write!(f, "INPUT"),
+            PinMode::Output => write!(f, "OUTPUT"),
+            PinMode::Analog => write!(f, "ANALOG"),
+            PinMode::Pwm => write!(f, "PWM"),
+            PinMode::Servo => write!(f, "SERVO"),
+            PinMode::Shift => write!(f, "SHIFT"),
+            PinMode::I2C => write!(f, "I2C"),
+            PinMode::OneWire => write!(f, "ONEWIRE"),
+            PinMode::Stepper => write!(f, "STEPPER"),
+            PinMode::Encoder => write!(f, "ENCODER"),
+            PinMode::Serial => write!(f, "SERIAL"),
+            PinMode::Pullup => write!(f, "PULLUP"),
+            PinMode::Ignore => write!(f, "IGNORE"),
+            PinMode::Unknown(mode) => write!(f, "UNKNOWN({mode:#04X})"),
+        }
+    }
+}
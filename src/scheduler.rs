@@ -0,0 +1,28 @@
+//! Tasks run by the scheduler of the firmware.
+
+/// State of a scheduler task as reported by the board.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchedulerTask {
+    /// Time in milliseconds at which the task runs next.
+    pub time: u32,
+    /// Length of the task body in bytes.
+    pub length: u16,
+    /// Position in the task body the task continues from.
+    pub position: u16,
+    /// Task body, the encoded commands the task runs.
+    pub data: Vec<u8>,
+}
+impl SchedulerTask {
+    /// Decode the state of a task from the unpacked bytes of a reply, `None` if it is empty.
+    pub(crate) fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 {
+            return None;
+        }
+        Some(Self {
+            time: u32::from_le_bytes([data[0], data[1], data[2], data[3]]),
+            length: u16::from_le_bytes([data[4], data[5]]),
+            position: u16::from_le_bytes([data[6], data[7]]),
+            data: data[8..].to_vec(),
+        })
+    }
+}
@@ -0,0 +1,88 @@
+//! Serial ports of the board, tunneled through the Firmata connection.
+
+use crate::*;
+use std::fmt;
+
+/// A hardware or software serial port of the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SerialPortId {
+    /// Hardware serial port 0 to 3, e.g. `Serial1` on an Arduino Mega is `Hardware(1)`.
+    Hardware(u8),
+    /// Software serial port 0 to 3.
+    Software(u8),
+}
+impl TryFrom<u8> for SerialPortId {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0x00..=0x03 => Ok(SerialPortId::Hardware(value - HW_SERIAL0)),
+            0x08..=0x0B => Ok(SerialPortId::Software(value - SW_SERIAL0)),
+            byte => Err(Error::BadByte { byte }),
+        }
+    }
+}
+impl From<SerialPortId> for u8 {
+    fn from(value: SerialPortId) -> Self {
+        match value {
+            SerialPortId::Hardware(port) => HW_SERIAL0 + (port & 0x03),
+            SerialPortId::Software(port) => SW_SERIAL0 + (port & 0x03),
+        }
+    }
+}
+impl fmt::Display for SerialPortId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerialPortId::Hardware(port) => write!(f, "HW_SERIAL{port}"),
+            SerialPortId::Software(port) => write!(f, "SW_SERIAL{port}"),
+        }
+    }
+}
+
+/// A serial port of the board that can be read and written like any other [`Read`] + [`Write`].
+///
+/// Configure the port with [`Firmata::serial_config`] and start reading with
+/// [`Firmata::serial_read_continuously`] first. Reading blocks until the board sends data for the
+/// port or the connection times out. Other messages that arrive in the meantime are kept in
+/// [`Board::pending`].
+#[derive(Debug)]
+pub struct Serial<'a, T: Read + Write + fmt::Debug> {
+    board: &'a mut Board<T>,
+    port: SerialPortId,
+}
+impl<'a, T: Read + Write + fmt::Debug> Serial<'a, T> {
+    /// Creates a new `Serial` handle for `port` of the `board`.
+    pub fn new(board: &'a mut Board<T>, port: SerialPortId) -> Self {
+        Self { board, port }
+    }
+    /// The serial port of this handle.
+    pub fn port(&self) -> SerialPortId {
+        self.port
+    }
+}
+impl<T: Read + Write + fmt::Debug> Read for Serial<'_, T> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let data = self.board.serial_data.entry(self.port).or_default();
+            if !data.is_empty() {
+                return data.read(buf);
+            }
+            match self.board.read_valid_message()? {
+                Message::SerialReply { port, .. } if port == self.port => {}
+                message => self.board.pending.push_back(message),
+            }
+        }
+    }
+}
+impl<T: Read + Write + fmt::Debug> Write for Serial<'_, T> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.board.serial_write(self.port, buf)?;
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.board.connection.flush()
+    }
+}
@@ -0,0 +1,32 @@
+//! Shift registers driven by the `SHIFT_DATA` messages.
+
+use crate::*;
+
+/// Order in which the bits of each byte are shifted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    /// Least significant bit first.
+    LsbFirst,
+    /// Most significant bit first.
+    #[default]
+    MsbFirst,
+}
+impl TryFrom<u8> for BitOrder {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            LSB_FIRST => Ok(BitOrder::LsbFirst),
+            MSB_FIRST => Ok(BitOrder::MsbFirst),
+            byte => Err(Error::BadByte { byte }),
+        }
+    }
+}
+impl From<BitOrder> for u8 {
+    fn from(value: BitOrder) -> Self {
+        match value {
+            BitOrder::LsbFirst => LSB_FIRST,
+            BitOrder::MsbFirst => MSB_FIRST,
+        }
+    }
+}
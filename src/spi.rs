@@ -0,0 +1,112 @@
+//! SPI devices driven by the `SPI_DATA` messages of ConfigurableFirmata.
+
+use crate::*;
+use std::fmt;
+
+/// A device on an SPI channel of the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpiDevice {
+    /// SPI channel 0 to 7, 0 on most boards.
+    pub channel: u8,
+    /// Device ID 0 to 15, chosen by the host to tell devices on a channel apart.
+    pub id: u8,
+}
+impl SpiDevice {
+    /// Creates a new `SpiDevice` for device `id` on `channel`.
+    pub fn new(channel: u8, id: u8) -> Self {
+        Self { channel, id }
+    }
+}
+impl From<u8> for SpiDevice {
+    fn from(value: u8) -> Self {
+        Self {
+            channel: value & 0x07,
+            id: (value >> 3) & 0x0F,
+        }
+    }
+}
+impl From<SpiDevice> for u8 {
+    fn from(value: SpiDevice) -> Self {
+        (value.id & 0x0F) << 3 | (value.channel & 0x07)
+    }
+}
+impl fmt::Display for SpiDevice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SPI{}.{}", self.channel, self.id)
+    }
+}
+
+/// Clock polarity and phase of an SPI device.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SpiMode {
+    /// Clock idles low, data is sampled on the rising edge.
+    #[default]
+    Mode0,
+    /// Clock idles low, data is sampled on the falling edge.
+    Mode1,
+    /// Clock idles high, data is sampled on the falling edge.
+    Mode2,
+    /// Clock idles high, data is sampled on the rising edge.
+    Mode3,
+}
+impl From<u8> for SpiMode {
+    fn from(value: u8) -> Self {
+        match value & 0x03 {
+            0 => SpiMode::Mode0,
+            1 => SpiMode::Mode1,
+            2 => SpiMode::Mode2,
+            _ => SpiMode::Mode3,
+        }
+    }
+}
+impl From<SpiMode> for u8 {
+    fn from(value: SpiMode) -> Self {
+        value as u8
+    }
+}
+
+/// Configuration of an SPI device.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SpiDeviceConfig {
+    pub mode: SpiMode,
+    pub bit_order: BitOrder,
+    /// Maximum clock speed in Hz.
+    pub max_speed: u32,
+    /// Chip select pin the firmware drives around each transfer, if it should drive one.
+    pub cs_pin: Option<u8>,
+    /// Whether the chip select pin is active high instead of active low.
+    pub cs_active_high: bool,
+}
+impl SpiDeviceConfig {
+    /// Creates a new `SpiDeviceConfig` in mode 0, most significant bit first, without a chip
+    /// select pin.
+    pub fn new(max_speed: u32) -> Self {
+        Self {
+            mode: SpiMode::Mode0,
+            bit_order: BitOrder::MsbFirst,
+            max_speed,
+            cs_pin: None,
+            cs_active_high: false,
+        }
+    }
+    /// Use SPI `mode`.
+    pub fn mode(mut self, mode: SpiMode) -> Self {
+        self.mode = mode;
+        self
+    }
+    /// Shift bits in `bit_order`.
+    pub fn bit_order(mut self, bit_order: BitOrder) -> Self {
+        self.bit_order = bit_order;
+        self
+    }
+    /// Let the firmware select the device through `cs_pin`.
+    pub fn cs_pin(mut self, cs_pin: u8) -> Self {
+        self.cs_pin = Some(cs_pin);
+        self
+    }
+    /// Drive the chip select pin high instead of low to select the device.
+    pub fn cs_active_high(mut self) -> Self {
+        self.cs_active_high = true;
+        self
+    }
+}
@@ -0,0 +1,80 @@
+//! Steppers driven by the legacy `STEPPER_DATA` messages.
+
+use crate::*;
+use std::fmt;
+
+/// How a stepper is wired to the board.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StepperInterface {
+    /// Step and direction driver, such as the EasyDriver.
+    Driver { direction_pin: u8, step_pin: u8 },
+    /// Two wire H-bridge.
+    TwoWire { pins: [u8; 2] },
+    /// Four wire H-bridge.
+    FourWire { pins: [u8; 4] },
+}
+impl StepperInterface {
+    /// All pins the stepper uses.
+    pub fn pins(&self) -> Vec<u8> {
+        match self {
+            StepperInterface::Driver {
+                direction_pin,
+                step_pin,
+            } => vec![*direction_pin, *step_pin],
+            StepperInterface::TwoWire { pins } => pins.to_vec(),
+            StepperInterface::FourWire { pins } => pins.to_vec(),
+        }
+    }
+}
+
+/// Direction of a stepper move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum StepperDirection {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Acceleration and deceleration of a stepper move, in 0.01 rad/s².
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct StepperRamp {
+    pub acceleration: u16,
+    pub deceleration: u16,
+}
+
+/// A stepper attached to the board, moved through the Firmata connection.
+///
+/// Configure the stepper with [`Firmata::stepper_config`] first.
+#[derive(Debug)]
+pub struct Stepper<'a, T: Read + Write + fmt::Debug> {
+    board: &'a mut Board<T>,
+    device: u8,
+}
+impl<'a, T: Read + Write + fmt::Debug> Stepper<'a, T> {
+    /// Creates a new `Stepper` handle for `device` of the `board`.
+    pub fn new(board: &'a mut Board<T>, device: u8) -> Self {
+        Self { board, device }
+    }
+    /// The device number of the stepper.
+    pub fn device(&self) -> u8 {
+        self.device
+    }
+    /// How the stepper is wired, if it has been configured.
+    pub fn interface(&self) -> Option<StepperInterface> {
+        self.board.steppers.get(&self.device).copied()
+    }
+    /// Move `steps` steps, counterclockwise if negative, at `speed` in 0.01 rad/s, with an
+    /// optional acceleration and deceleration `ramp`.
+    pub fn step(&mut self, steps: i32, speed: i32, ramp: Option<StepperRamp>) -> Result<()> {
+        self.board
+            .stepper_step(self.device as i32, steps, speed, ramp)
+    }
+    /// Wait up to `timeout` for the board to report that the current move is complete.
+    pub fn wait(&mut self, timeout: Duration) -> Result<()> {
+        let device = self.device;
+        self.board.wait_for(
+            timeout,
+            |message| matches!(message, Message::StepperMoveComplete { device: d } if *d == device),
+        )?;
+        Ok(())
+    }
+}
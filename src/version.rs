@@ -0,0 +1,69 @@
+//! Protocol and firmware versions and the protocol features they support.
+
+use std::fmt;
+
+/// Firmata protocol version as reported by the board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+impl ProtocolVersion {
+    /// Creates a new `ProtocolVersion`.
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self { major, minor }
+    }
+}
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Firmware version as reported by the board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FirmwareVersion {
+    pub major: u8,
+    pub minor: u8,
+}
+impl FirmwareVersion {
+    /// Creates a new `FirmwareVersion`.
+    pub const fn new(major: u8, minor: u8) -> Self {
+        Self { major, minor }
+    }
+}
+impl fmt::Display for FirmwareVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// Protocol features that are not supported by every protocol version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Feature {
+    /// The `PIN_MODE_PULLUP` pin mode.
+    Pullup,
+    /// The `SET_DIGITAL_PIN_VALUE` message.
+    SetDigitalPinValue,
+    /// The `SERIAL_DATA` messages.
+    Serial,
+}
+impl Feature {
+    /// First protocol version that supports the feature.
+    pub fn since(self) -> ProtocolVersion {
+        match self {
+            Feature::Pullup => ProtocolVersion::new(2, 5),
+            Feature::SetDigitalPinValue => ProtocolVersion::new(2, 5),
+            Feature::Serial => ProtocolVersion::new(2, 5),
+        }
+    }
+}
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Feature::Pullup => write!(f, "PIN_MODE_PULLUP"),
+            Feature::SetDigitalPinValue => write!(f, "SET_DIGITAL_PIN_VALUE"),
+            Feature::Serial => write!(f, "SERIAL_DATA"),
+        }
+    }
+}